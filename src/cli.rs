@@ -1,5 +1,12 @@
 // 命令行参数解析
-use clap::{Parser, Subcommand};
+use crate::ssh::known_hosts::HostKeyPolicy;
+use crate::transfer::conflict::ConflictPolicy;
+use crate::transfer::compression::StreamCompression;
+use crate::transfer::links::LinkPolicy;
+use crate::transfer::sync::SyncDirection;
+use crate::utils::file::HashAlgorithm;
+use crate::utils::rate::parse_rate;
+use clap::{Args, Parser, Subcommand};
 use std::path::PathBuf;
 
 #[derive(Parser)]
@@ -43,6 +50,110 @@ pub struct Cli {
     #[arg(short, long)]
     pub resume: bool,
 
+    /// What to do when the destination file already exists
+    #[arg(long, value_enum, default_value = "overwrite")]
+    pub on_conflict: ConflictPolicy,
+
+    /// Verify file integrity by comparing local and remote hashes after transfer
+    #[arg(long)]
+    pub verify: bool,
+
+    /// Hash algorithm for verification and resume chunk checksums
+    #[arg(long = "hash", value_name = "HASH", value_enum, default_value = "md5")]
+    pub hash_algorithm: HashAlgorithm,
+
+    /// Send only the changed blocks of files that already exist at the destination (requires python3 on the remote host)
+    #[arg(long)]
+    pub delta: bool,
+
+    /// Print what would be transferred, skipped or deleted without changing anything
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Only transfer files matching this glob pattern in recursive transfers (repeatable)
+    #[arg(long, value_name = "PATTERN")]
+    pub include: Vec<String>,
+
+    /// Skip files and directories matching this glob pattern in recursive transfers (repeatable)
+    #[arg(long, value_name = "PATTERN")]
+    pub exclude: Vec<String>,
+
+    /// Read exclude patterns from a file, one per line
+    #[arg(long, value_name = "FILE")]
+    pub exclude_from: Option<PathBuf>,
+
+    /// Skip files ignored by .gitignore, .ignore, .git/info/exclude and global git excludes when uploading directories, including rules from parent directories up to the repository root
+    #[arg(long)]
+    pub respect_gitignore: bool,
+
+    /// How to handle symlinks in recursive transfers: recreate the link, copy the target contents, or skip it
+    #[arg(long, value_name = "MODE", value_enum, default_value = "follow")]
+    pub links: LinkPolicy,
+
+    /// Detect hard-linked files in recursive transfers, send their content once and recreate the other names as hard links
+    #[arg(long)]
+    pub hard_links: bool,
+
+    /// Skip holes when reading sparse files and keep the destination sparse by seeking over zero blocks
+    #[arg(long)]
+    pub sparse: bool,
+
+    /// Preallocate downloaded files at their full size before writing (ignored with --sparse)
+    #[arg(long)]
+    pub preallocate: bool,
+
+    /// Transfer the files of each directory as one tar stream over an exec channel (requires tar on the remote host; cannot be combined with --resume)
+    #[arg(long, conflicts_with = "resume")]
+    pub archive_mode: bool,
+
+    /// Enable SSH transport compression (zlib) for all channels of the session
+    #[arg(long)]
+    pub compress: bool,
+
+    /// Compress the tar stream of --archive-mode with gzip or zstd on the remote end
+    #[arg(long, value_name = "CODEC", value_enum, default_value = "none")]
+    pub stream_compression: StreamCompression,
+
+    /// Limit the total bandwidth of all worker threads, in bytes per second (K, M and G suffixes accepted, e.g. 10M)
+    #[arg(long, value_name = "RATE", value_parser = parse_rate)]
+    pub limit_rate: Option<u64>,
+
+    /// Limit the bandwidth of each individual file, in bytes per second (K, M and G suffixes accepted)
+    #[arg(long, value_name = "RATE", value_parser = parse_rate)]
+    pub limit_rate_per_file: Option<u64>,
+
+    /// File with time-of-day bandwidth limits, one "HH:MM-HH:MM RATE" or "default RATE" per line (RATE may be "unlimited")
+    #[arg(long, value_name = "FILE")]
+    pub bandwidth_schedule: Option<PathBuf>,
+
+    /// Retry files and chunks that fail with a transient (network or server) error this many times
+    #[arg(long, value_name = "N", default_value = "3")]
+    pub retries: u32,
+
+    /// Initial delay in seconds before a retry; doubles with every attempt, with random jitter
+    #[arg(long, value_name = "SECONDS", default_value = "1")]
+    pub retry_delay: f64,
+
+    /// Reconnect this many times when the SSH connection drops mid-transfer, then resume in-flight files (0 disables reconnecting)
+    #[arg(long, value_name = "N", default_value = "5")]
+    pub reconnect_attempts: u32,
+
+    /// Give up connecting to the server (TCP connect, SSH handshake and authentication) after this many seconds (0 waits indefinitely)
+    #[arg(long, value_name = "SECONDS", default_value = "30")]
+    pub connect_timeout: u64,
+
+    /// Fail an SSH or SFTP operation that waits this many seconds for the server (0 waits indefinitely)
+    #[arg(long, value_name = "SECONDS", default_value = "0")]
+    pub io_timeout: u64,
+
+    /// Send an SSH keepalive message every this many seconds while a connection is idle (0 disables keepalives)
+    #[arg(long, value_name = "SECONDS", default_value = "30")]
+    pub keepalive_interval: u32,
+
+    /// How to check the server's host key against ~/.ssh/known_hosts: only known hosts, record unknown hosts on first use, or no check
+    #[arg(long, value_enum, default_value = "accept-new")]
+    pub host_key_policy: HostKeyPolicy,
+
     /// Verbose output
     #[arg(short, long)]
     pub verbose: bool,
@@ -63,6 +174,8 @@ pub enum Commands {
         /// Recursively download directories
         #[arg(short, long)]
         recursive: bool,
+        #[command(flatten)]
+        preserve: PreserveArgs,
     },
     /// Upload files to remote server
     Upload {
@@ -73,5 +186,35 @@ pub enum Commands {
         /// Recursively upload directories
         #[arg(short, long)]
         recursive: bool,
+        #[command(flatten)]
+        preserve: PreserveArgs,
+    },
+    /// Synchronize a local directory with a remote directory, copying only the differences
+    Sync {
+        /// Local directory path
+        local_path: PathBuf,
+        /// Remote directory path
+        remote_path: String,
+        /// Push the local directory to the remote one, or pull the remote directory into the local one
+        #[arg(long, value_enum, default_value = "push")]
+        direction: SyncDirection,
+        /// Delete destination entries that do not exist in the source
+        #[arg(long)]
+        delete: bool,
+        /// Compare files of equal size by hash instead of modification time
+        #[arg(long)]
+        checksum: bool,
+        #[command(flatten)]
+        preserve: PreserveArgs,
     },
+}
+
+#[derive(Args, Debug, Clone, Copy)]
+pub struct PreserveArgs {
+    /// Preserve permissions and access/modification times
+    #[arg(short, long)]
+    pub preserve: bool,
+    /// Also preserve owner and group (implies --preserve, usually requires root on the destination)
+    #[arg(long)]
+    pub preserve_owner: bool,
 }
\ No newline at end of file
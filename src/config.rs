@@ -1,8 +1,19 @@
 // 配置管理
 use crate::cli::{Cli, Commands};
+use crate::ssh::known_hosts::HostKeyPolicy;
+use crate::transfer::attributes::AttributeSet;
+use crate::transfer::compression::StreamCompression;
+use crate::transfer::conflict::ConflictPolicy;
+use crate::transfer::links::LinkPolicy;
+use crate::transfer::sync::{SyncDirection, SyncOptions};
+use crate::utils::file::HashAlgorithm;
+use crate::utils::filter::PathFilter;
+use crate::utils::retry::RetryPolicy;
+use crate::utils::schedule::BandwidthSchedule;
 use anyhow::{Context, Result};
 use dialoguer::{Confirm, Password};
 use std::path::PathBuf;
+use std::time::Duration;
 
 #[derive(Debug, Clone)]
 pub struct Config {
@@ -13,6 +24,31 @@ pub struct Config {
     pub threads: usize,
     pub chunk_size: usize,
     pub resume: bool,
+    pub on_conflict: ConflictPolicy,
+    pub verify: bool,
+    pub hash_algorithm: HashAlgorithm,
+    pub delta: bool,
+    pub dry_run: bool,
+    pub filter: PathFilter,
+    pub respect_gitignore: bool,
+    pub links: LinkPolicy,
+    pub hard_links: bool,
+    pub sparse: bool,
+    pub preallocate: bool,
+    pub archive_mode: bool,
+    pub compress: bool,
+    pub stream_compression: StreamCompression,
+    pub limit_rate: Option<u64>,
+    pub limit_rate_per_file: Option<u64>,
+    pub bandwidth_schedule: Option<BandwidthSchedule>,
+    pub retry: RetryPolicy,
+    pub reconnect_attempts: u32,
+    pub connect_timeout: Option<Duration>,
+    pub io_timeout: Option<Duration>,
+    pub keepalive_interval: u32,
+    pub host_key_policy: HostKeyPolicy,
+    pub preserve: bool,
+    pub preserve_owner: bool,
     pub verbose: bool,
     pub operation: Operation,
 }
@@ -36,27 +72,53 @@ pub enum Operation {
         remote_path: String,
         recursive: bool,
     },
+    Sync {
+        local_path: PathBuf,
+        remote_path: String,
+        direction: SyncDirection,
+        delete: bool,
+        checksum: bool,
+    },
 }
 
 impl Config {
     pub fn from_cli(cli: &Cli) -> Result<Self> {
+        let filter = PathFilter::new(&cli.include, &cli.exclude, cli.exclude_from.as_deref())?;
+        let retry_delay = Duration::try_from_secs_f64(cli.retry_delay)
+            .context("--retry-delay must be a non-negative number of seconds")?;
+        let bandwidth_schedule = cli.bandwidth_schedule.as_deref().map(BandwidthSchedule::load).transpose()?;
         let auth = Self::determine_auth_method(cli)?;
 
+        let preserve = match &cli.command {
+            Commands::Download { preserve, .. }
+            | Commands::Upload { preserve, .. }
+            | Commands::Sync { preserve, .. } => *preserve,
+        };
+
         let operation = match &cli.command {
-            Commands::Download { remote_path, local_path, recursive } => {
+            Commands::Download { remote_path, local_path, recursive, .. } => {
                 Operation::Download {
                     remote_path: remote_path.clone(),
                     local_path: local_path.clone(),
                     recursive: *recursive,
                 }
             }
-            Commands::Upload { local_path, remote_path, recursive } => {
+            Commands::Upload { local_path, remote_path, recursive, .. } => {
                 Operation::Upload {
                     local_path: local_path.clone(),
                     remote_path: remote_path.clone(),
                     recursive: *recursive,
                 }
             }
+            Commands::Sync { local_path, remote_path, direction, delete, checksum, .. } => {
+                Operation::Sync {
+                    local_path: local_path.clone(),
+                    remote_path: remote_path.clone(),
+                    direction: *direction,
+                    delete: *delete,
+                    checksum: *checksum,
+                }
+            }
         };
 
         Ok(Config {
@@ -67,11 +129,53 @@ impl Config {
             threads: cli.threads,
             chunk_size: cli.chunk_size,
             resume: cli.resume,
+            on_conflict: cli.on_conflict,
+            verify: cli.verify,
+            hash_algorithm: cli.hash_algorithm,
+            delta: cli.delta,
+            dry_run: cli.dry_run,
+            filter,
+            respect_gitignore: cli.respect_gitignore,
+            links: cli.links,
+            hard_links: cli.hard_links,
+            sparse: cli.sparse,
+            preallocate: cli.preallocate,
+            archive_mode: cli.archive_mode,
+            compress: cli.compress,
+            stream_compression: cli.stream_compression,
+            limit_rate: cli.limit_rate,
+            limit_rate_per_file: cli.limit_rate_per_file,
+            bandwidth_schedule,
+            retry: RetryPolicy::new(cli.retries, retry_delay),
+            reconnect_attempts: cli.reconnect_attempts,
+            connect_timeout: seconds(cli.connect_timeout),
+            io_timeout: seconds(cli.io_timeout),
+            keepalive_interval: cli.keepalive_interval,
+            host_key_policy: cli.host_key_policy,
+            preserve: preserve.preserve || preserve.preserve_owner,
+            preserve_owner: preserve.preserve_owner,
             verbose: cli.verbose,
             operation,
         })
     }
 
+    // sync 命令的比较和删除选项，其他命令返回 None
+    pub fn sync_options(&self) -> Option<SyncOptions> {
+        match self.operation {
+            Operation::Sync { delete, checksum, .. } => Some(SyncOptions { delete, checksum }),
+            _ => None,
+        }
+    }
+
+    // 传输完成后需要应用到目标的属性；同步时总是保留修改时间，供下次比较
+    pub fn preserved_attributes(&self) -> AttributeSet {
+        AttributeSet {
+            mode: self.preserve,
+            times: self.preserve || self.sync_options().is_some(),
+            owner: self.preserve_owner,
+        }
+    }
+
     fn determine_auth_method(cli: &Cli) -> Result<AuthMethod> {
         // 如果命令行提供了密码，直接使用
         if let Some(password) = &cli.password {
@@ -117,4 +221,9 @@ impl Config {
 
         Ok(AuthMethod::Password(password))
     }
-}
\ No newline at end of file
+}
+
+// 以秒为单位的超时选项，0 表示不限时
+fn seconds(value: u64) -> Option<Duration> {
+    (value > 0).then(|| Duration::from_secs(value))
+}
@@ -8,17 +8,47 @@ pub mod threadpool;
 
 use anyhow::Result;
 use config::Config;
-use transfer::{download::Downloader, upload::Uploader};
+use transfer::{download::Downloader, plan::TransferPlan, summary::TransferSummary, sync::SyncDirection, upload::Uploader};
 
 pub async fn run_transfer(config: Config) -> Result<()> {
     match config.operation.clone() {
         config::Operation::Download { remote_path, local_path, recursive } => {
             let downloader = Downloader::new(config)?;
-            downloader.download(&remote_path, &local_path, recursive).await
+            let result = downloader.download(&remote_path, &local_path, recursive).await;
+            finish_with_summary(result, downloader.summary(), downloader.plan())
         }
         config::Operation::Upload { local_path, remote_path, recursive } => {
             let uploader = Uploader::new(config)?;
-            uploader.upload(&local_path, &remote_path, recursive).await
+            let result = uploader.upload(&local_path, &remote_path, recursive).await;
+            finish_with_summary(result, uploader.summary(), uploader.plan())
         }
+        config::Operation::Sync { local_path, remote_path, direction, .. } => match direction {
+            SyncDirection::Push => {
+                let uploader = Uploader::new(config)?;
+                let result = uploader.sync(&local_path, &remote_path).await;
+                finish_with_summary(result, uploader.summary(), uploader.plan())
+            }
+            SyncDirection::Pull => {
+                let downloader = Downloader::new(config)?;
+                let result = downloader.sync(&remote_path, &local_path).await;
+                finish_with_summary(result, downloader.summary(), downloader.plan())
+            }
+        },
     }
 }
+
+// 打印传输汇总，有文件失败时整个传输视为失败；试运行时打印传输计划
+fn finish_with_summary(result: Result<()>, summary: &TransferSummary, plan: &TransferPlan) -> Result<()> {
+    if plan.is_enabled() {
+        plan.print();
+        return result;
+    }
+    summary.print();
+    result?;
+
+    let failed = summary.failed_count();
+    if failed > 0 {
+        return Err(anyhow::anyhow!("{} file(s) failed to transfer", failed));
+    }
+    Ok(())
+}
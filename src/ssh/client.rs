@@ -1,28 +1,90 @@
 // SSH客户端封装
 use crate::config::{AuthMethod, Config};
-use crate::utils::error::TransferError;
+use crate::ssh::known_hosts::{prefer_known_key_types, verify_host_key};
+use crate::utils::error::{is_disconnect, is_transient, timeout_error, TransferError};
+use crate::utils::retry::RetryPolicy;
 use anyhow::{Context, Result};
-use ssh2::Session;
+use crossbeam_channel::{bounded, RecvTimeoutError, Sender};
+use ssh2::{Session, Sftp};
 use std::io::prelude::*;
-use std::net::TcpStream;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::Mutex;
+use std::time::Duration;
 
+// 连接断开后可以原地重新连接，之前打开的 SFTP 通道和远程文件随旧连接失效，需要调用方重新打开
 pub struct SshClient {
-    pub session: Session,
+    connection: Mutex<Connection>,
+    config: Config,
+}
+
+// 已认证的会话和它的保活线程；连接被替换或释放时发送端随之关闭，保活线程退出
+struct Connection {
+    session: Session,
+    _keepalive: Option<Sender<()>>,
 }
 
 impl SshClient {
     pub fn connect(config: &Config) -> Result<Self> {
+        let connection = Self::open(config)?;
+        Ok(SshClient { connection: Mutex::new(connection), config: config.clone() })
+    }
+
+    // 建立 TCP 连接、握手并认证
+    fn open(config: &Config) -> Result<Connection> {
         // println!("Connecting to {}:{}...", config.host, config.port);
+        let address = format!("{}:{}", config.host, config.port);
         
-        let tcp = TcpStream::connect(format!("{}:{}", config.host, config.port))
-            .context("Failed to connect to SSH server")?;
+        let tcp = Self::connect_tcp(config, &address)?;
+        tcp.set_read_timeout(config.io_timeout)?;
+        tcp.set_write_timeout(config.io_timeout)?;
         
         let mut session = Session::new().context("Failed to create SSH session")?;
         session.set_tcp_stream(tcp);
-        session.handshake().context("SSH handshake failed")?;
+        // 压缩需要在握手协商算法之前开启
+        session.set_compress(config.compress);
+        prefer_known_key_types(&session, &config.host, config.port, config.host_key_policy)?;
+        // 握手和认证受 --connect-timeout 限制，之后的每个操作受 --io-timeout 限制
+        session.set_timeout(timeout_millis(config.connect_timeout));
+        session.handshake()
+            .context("SSH handshake failed")
+            .map_err(|e| timeout_error(e, &format!("SSH handshake with {}", address), config.connect_timeout))?;
+
+        // 认证信息只发给主机密钥校验通过的服务器
+        verify_host_key(&session, &config.host, config.port, config.host_key_policy)?;
 
         // println!("SSH handshake completed. Authenticating...");
+        Self::authenticate(&session, config)
+            .map_err(|e| timeout_error(e, &format!("SSH authentication with {}", address), config.connect_timeout))?;
+        session.set_timeout(timeout_millis(config.io_timeout));
+
+        // println!("Authentication successful!");
+        let keepalive = Self::start_keepalive(&session, config.keepalive_interval);
+        Ok(Connection { session, _keepalive: keepalive })
+    }
+
+    // 依次尝试主机名解析出的每个地址，每个地址最多等待 --connect-timeout
+    fn connect_tcp(config: &Config, address: &str) -> Result<TcpStream> {
+        let addrs = (config.host.as_str(), config.port).to_socket_addrs()
+            .with_context(|| format!("Failed to resolve {}", address))?;
+        let mut last_error = None;
+        for addr in addrs {
+            let result = match config.connect_timeout {
+                Some(timeout) => TcpStream::connect_timeout(&addr, timeout),
+                None => TcpStream::connect(addr),
+            };
+            match result {
+                Ok(tcp) => return Ok(tcp),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        let error = match last_error {
+            Some(e) => anyhow::Error::from(e),
+            None => anyhow::anyhow!("{} did not resolve to any address", address),
+        };
+        Err(timeout_error(error.context("Failed to connect to SSH server"), &format!("Connection to {}", address), config.connect_timeout))
+    }
 
+    fn authenticate(session: &Session, config: &Config) -> Result<()> {
         // Authentication
         match &config.auth {
             AuthMethod::Password(password) => {
@@ -48,17 +110,122 @@ impl SshClient {
         if !session.authenticated() {
             return Err(TransferError::AuthenticationFailed.into());
         }
+        Ok(())
+    }
 
-        // println!("Authentication successful!");
-        Ok(SshClient { session })
+    // 每隔 --keepalive-interval 秒发送一次 SSH 保活消息，防止空闲连接被防火墙或 NAT 断开
+    // libssh2 只在调用 keepalive_send 时发送，由单独的线程驱动；返回的发送端被丢弃时线程退出
+    fn start_keepalive(session: &Session, interval: u32) -> Option<Sender<()>> {
+        if interval == 0 {
+            return None;
+        }
+        session.set_keepalive(false, interval);
+        let session = session.clone();
+        let (stop, stopped) = bounded::<()>(0);
+        std::thread::spawn(move || {
+            let mut wait = interval;
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(Duration::from_secs(wait as u64)) {
+                match session.keepalive_send() {
+                    // 返回距离下一次需要发送的秒数，期间有其他数据收发时会推迟
+                    Ok(next) => wait = next.max(1),
+                    Err(e) => {
+                        log::debug!("Stopping SSH keepalives: {}", e);
+                        break;
+                    }
+                }
+            }
+        });
+        Some(stop)
     }
 
-    pub fn sftp(&self) -> Result<ssh2::Sftp> {
-        self.session.sftp().context("Failed to create SFTP session")
+    // ssh2::Session 内部是引用计数的句柄，克隆后在锁外使用
+    fn session(&self) -> Session {
+        self.connection.lock().unwrap().session.clone()
+    }
+
+    pub fn sftp(&self) -> Result<Sftp> {
+        self.session().sftp().context("Failed to create SFTP session")
+    }
+
+    // 操作失败后检查连接，连接已断开时重新连接；返回 true 表示已重新连接
+    pub fn recover(&self, error: &anyhow::Error) -> Result<bool> {
+        if !is_transient(error) {
+            return Ok(false);
+        }
+        // 错误本身无法说明连接状态时，打开一个 SFTP 通道确认连接是否可用
+        if !is_disconnect(error) && self.session().sftp().is_ok() {
+            return Ok(false);
+        }
+        self.reconnect(error)?;
+        Ok(true)
+    }
+
+    // 按 --retry-delay 退避重新连接，最多尝试 --reconnect-attempts 次
+    pub fn reconnect(&self, error: &anyhow::Error) -> Result<()> {
+        let address = format!("{}:{}", self.config.host, self.config.port);
+        let attempts = self.config.reconnect_attempts;
+        let mut reason = error.to_string();
+        for attempt in 0..attempts {
+            let delay = self.config.retry.backoff(attempt);
+            eprintln!("Connection to {} lost: {}; reconnecting in {:.1}s ({}/{})",
+                      address, reason, delay.as_secs_f64(), attempt + 1, attempts);
+            std::thread::sleep(delay);
+            match Self::open(&self.config) {
+                Ok(connection) => {
+                    *self.connection.lock().unwrap() = connection;
+                    eprintln!("Reconnected to {}", address);
+                    log::info!("Reconnected to {} after {} attempt(s)", address, attempt + 1);
+                    return Ok(());
+                }
+                // 主机密钥不符、认证失败等错误重连也不会成功
+                Err(e) if !is_transient(&e) => return Err(e),
+                Err(e) => reason = format!("{:#}", e),
+            }
+        }
+        Err(TransferError::ConnectionLost { address, attempts }.into())
+    }
+
+    // 执行 operation，临时错误按 retry 重试；连接断开时先重新连接，重新连接不计入重试次数
+    // sftp 为 None 时（工作线程首次使用或重新连接之后）在循环内打开，打开失败与操作失败同样处理
+    // operation 的第二个参数为 true 表示之前的尝试失败过，应从已确认的位置续传
+    pub fn run_reconnecting<T>(
+        &self,
+        retry: &RetryPolicy,
+        what: &str,
+        sftp: &mut Option<Sftp>,
+        mut operation: impl FnMut(&Sftp, bool) -> Result<T>,
+    ) -> Result<T> {
+        let mut attempt = 0;
+        let mut reconnects = 0;
+        let mut failed = false;
+        loop {
+            let result = match sftp {
+                Some(sftp) => operation(sftp, failed),
+                None => self.sftp().and_then(|opened| operation(sftp.insert(opened), failed)),
+            };
+            let error = match result {
+                Ok(value) => return Ok(value),
+                Err(e) => timeout_error(e, what, self.config.io_timeout),
+            };
+            failed = true;
+            if reconnects < self.config.reconnect_attempts && self.recover(&error)? {
+                *sftp = None;
+                reconnects += 1;
+                continue;
+            }
+            match retry.should_retry(attempt, &error) {
+                Some(delay) => {
+                    retry.report(what, &error, attempt, delay);
+                    std::thread::sleep(delay);
+                    attempt += 1;
+                }
+                None => return Err(error),
+            }
+        }
     }
 
     pub fn exec(&self, command: &str) -> Result<String> {
-        let mut channel = self.session.channel_session()
+        let mut channel = self.session().channel_session()
             .context("Failed to create SSH channel")?;
         
         channel.exec(command)
@@ -73,4 +240,25 @@ impl SshClient {
         
         Ok(output)
     }
-}
\ No newline at end of file
+
+    // 启动远程命令并返回通道，由调用方读写命令的标准输入输出
+    pub fn exec_channel(&self, command: &str) -> Result<ssh2::Channel> {
+        let mut channel = self.session().channel_session()
+            .context("Failed to create SSH channel")?;
+
+        channel.exec(command)
+            .context("Failed to execute command")?;
+
+        Ok(channel)
+    }
+}
+
+// libssh2 以毫秒为单位，0 表示不限时
+fn timeout_millis(timeout: Option<Duration>) -> u32 {
+    timeout.map(|t| t.as_millis().min(u32::MAX as u128) as u32).unwrap_or(0)
+}
+
+// 为远程shell命令转义参数
+pub fn shell_quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', "'\\''"))
+}
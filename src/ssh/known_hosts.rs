@@ -0,0 +1,271 @@
+// 主机密钥校验：对照 ~/.ssh/known_hosts 检查服务器的主机密钥，防止中间人攻击
+use crate::utils::error::TransferError;
+use anyhow::{bail, Context, Result};
+use sha2::{Digest, Sha256};
+use ssh2::{CheckResult, HostKeyType, KnownHostFileKind, KnownHosts, MethodType, Session};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum HostKeyPolicy {
+    // 只接受 known_hosts 中已有的主机
+    Strict,
+    // 首次连接时记录主机密钥，之后密钥变化则拒绝连接
+    #[default]
+    AcceptNew,
+    // 不检查主机密钥
+    Off,
+}
+
+// 握手之前调用：把 known_hosts 中为该主机记录过的密钥类型排在协商顺序最前面，
+// 避免服务器另有一种未记录的密钥类型时协商到它
+pub fn prefer_known_key_types(session: &Session, host: &str, port: u16, policy: HostKeyPolicy) -> Result<()> {
+    if policy == HostKeyPolicy::Off {
+        return Ok(());
+    }
+    let Some(content) = read_known_hosts()? else {
+        return Ok(());
+    };
+
+    let supported = session.supported_algs(MethodType::HostKey)
+        .context("Failed to list supported host key algorithms")?;
+    let preferred: Vec<&str> = recorded_key_types(session, &content, host, port)?
+        .into_iter()
+        .flat_map(|type_name| algorithms(type_name).iter().copied())
+        .filter(|alg| supported.contains(alg))
+        .collect();
+    if preferred.is_empty() {
+        return Ok(());
+    }
+    // 其余算法放在后面，服务器不再提供已记录的类型时仍能握手
+    let rest = supported.iter().filter(|alg| !preferred.contains(alg)).copied();
+    let prefs: Vec<&str> = preferred.iter().copied().chain(rest).collect();
+    session.method_pref(MethodType::HostKey, &prefs.join(","))
+        .context("Failed to set host key algorithm preference")?;
+    Ok(())
+}
+
+// 握手完成后、发送认证信息之前调用
+pub fn verify_host_key(session: &Session, host: &str, port: u16, policy: HostKeyPolicy) -> Result<()> {
+    if policy == HostKeyPolicy::Off {
+        return Ok(());
+    }
+
+    let (key, key_type) = session.host_key().context("Server did not send a host key")?;
+    let path = known_hosts_path()?;
+    let content = read_known_hosts()?.unwrap_or_default();
+    let name = host_entry_name(host, port);
+    match check_key(session, &content, host, port, key, key_type)? {
+        CheckResult::Match => Ok(()),
+        CheckResult::Mismatch => Err(TransferError::HostKeyMismatch {
+            host: name,
+            fingerprint: fingerprint(key),
+            known_hosts: path.display().to_string(),
+        }.into()),
+        CheckResult::NotFound if policy == HostKeyPolicy::Strict => Err(TransferError::HostKeyUnknown {
+            host: name,
+            fingerprint: fingerprint(key),
+            known_hosts: path.display().to_string(),
+        }.into()),
+        CheckResult::NotFound => {
+            append_entry(&path, &name, key, key_type)?;
+            println!("Permanently added {} ({} key {}) to {}",
+                     name, key_type_name(key_type).unwrap_or("unknown"), fingerprint(key), path.display());
+            Ok(())
+        }
+        CheckResult::Failure => bail!("Failed to check the host key of {} against {}", name, path.display()),
+    }
+}
+
+// known_hosts 中为该主机记录过的密钥类型
+fn recorded_key_types(session: &Session, content: &str, host: &str, port: u16) -> Result<Vec<&'static str>> {
+    let mut recorded = Vec::new();
+    for type_name in KEY_TYPES {
+        // 用一个不可能匹配的密钥查询：Mismatch 说明该类型下记录过这个主机，哈希条目也适用
+        let known_hosts = load_entries(session, content, Some(type_name))?;
+        if matches!(known_hosts.check_port(host, port, &[0]), CheckResult::Mismatch) {
+            recorded.push(type_name);
+        }
+    }
+    Ok(recorded)
+}
+
+// ssh2 不向 libssh2 传递密钥类型，libssh2 会拿服务器密钥和该主机所有类型的条目比较，
+// 只记录了 ed25519 而协商到 ecdsa 时会误报 Mismatch，所以只加载与协商类型相同的条目
+fn check_key(session: &Session, content: &str, host: &str, port: u16, key: &[u8], key_type: HostKeyType) -> Result<CheckResult> {
+    let known_hosts = load_entries(session, content, key_type_name(key_type))?;
+    Ok(known_hosts.check_port(host, port, key))
+}
+
+// known_hosts 中出现的密钥类型
+const KEY_TYPES: [&str; 6] = [
+    "ssh-ed25519",
+    "ecdsa-sha2-nistp256",
+    "ecdsa-sha2-nistp384",
+    "ecdsa-sha2-nistp521",
+    "ssh-rsa",
+    "ssh-dss",
+];
+
+// 密钥类型对应的握手算法名，RSA 密钥可以用三种签名算法
+fn algorithms(type_name: &str) -> &'static [&'static str] {
+    match type_name {
+        "ssh-ed25519" => &["ssh-ed25519"],
+        "ecdsa-sha2-nistp256" => &["ecdsa-sha2-nistp256"],
+        "ecdsa-sha2-nistp384" => &["ecdsa-sha2-nistp384"],
+        "ecdsa-sha2-nistp521" => &["ecdsa-sha2-nistp521"],
+        "ssh-rsa" => &["rsa-sha2-512", "rsa-sha2-256", "ssh-rsa"],
+        "ssh-dss" => &["ssh-dss"],
+        _ => &[],
+    }
+}
+
+fn read_known_hosts() -> Result<Option<String>> {
+    let path = known_hosts_path()?;
+    if !path.exists() {
+        return Ok(None);
+    }
+    let data = std::fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(Some(String::from_utf8_lossy(&data).into_owned()))
+}
+
+// 逐行加载 known_hosts，type_name 为 None 时加载所有类型
+// libssh2 能识别 HashKnownHosts 生成的 |1|salt|hash 条目；@cert-authority 等带标记的行跳过
+fn load_entries(session: &Session, content: &str, type_name: Option<&str>) -> Result<KnownHosts> {
+    let mut known_hosts = session.known_hosts().context("Failed to initialize known hosts")?;
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') || line.starts_with('@') {
+            continue;
+        }
+        let line_type = line.split_whitespace().nth(1);
+        if type_name.is_some_and(|name| line_type != Some(name)) {
+            continue;
+        }
+        // 无法解析的行与 OpenSSH 一样忽略
+        let _ = known_hosts.read_str(line, KnownHostFileKind::OpenSSH);
+    }
+    Ok(known_hosts)
+}
+
+// 与 ssh-keygen -l 相同的 SHA256 指纹：密钥的 SHA256 摘要，base64 编码后去掉填充
+pub fn fingerprint(key: &[u8]) -> String {
+    format!("SHA256:{}", base64(&Sha256::digest(key)).trim_end_matches('='))
+}
+
+fn known_hosts_path() -> Result<PathBuf> {
+    let home = home::home_dir().context("Cannot determine home directory")?;
+    Ok(home.join(".ssh").join("known_hosts"))
+}
+
+// known_hosts 中非默认端口的主机写作 [host]:port
+fn host_entry_name(host: &str, port: u16) -> String {
+    if port == 22 {
+        host.to_string()
+    } else {
+        format!("[{}]:{}", host, port)
+    }
+}
+
+fn key_type_name(key_type: HostKeyType) -> Option<&'static str> {
+    match key_type {
+        HostKeyType::Rsa => Some("ssh-rsa"),
+        HostKeyType::Dss => Some("ssh-dss"),
+        HostKeyType::Ecdsa256 => Some("ecdsa-sha2-nistp256"),
+        HostKeyType::Ecdsa384 => Some("ecdsa-sha2-nistp384"),
+        HostKeyType::Ecdsa521 => Some("ecdsa-sha2-nistp521"),
+        HostKeyType::Ed25519 => Some("ssh-ed25519"),
+        HostKeyType::Unknown => None,
+    }
+}
+
+// 只在文件末尾追加一行，不改写用户已有的条目和注释
+fn append_entry(path: &Path, name: &str, key: &[u8], key_type: HostKeyType) -> Result<()> {
+    let type_name = key_type_name(key_type).context("Server sent a host key of unknown type")?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    // 原文件最后一行没有换行符时先补上
+    let needs_newline = std::fs::read(path).map(|data| data.last().is_some_and(|&b| b != b'\n')).unwrap_or(false);
+    let line = format!("{}{} {} {}\n", if needs_newline { "\n" } else { "" }, name, type_name, base64(key));
+    file.write_all(line.as_bytes())
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok(())
+}
+
+// 标准 base64 编码（带填充）
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [chunk[0], chunk.get(1).copied().unwrap_or(0), chunk.get(2).copied().unwrap_or(0)];
+        let group = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(group >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ssh-keygen -t ed25519 生成的公钥
+    const ED25519_BASE64: &str = "AAAAC3NzaC1lZDI1NTE5AAAAIMNKsnyBow5IrRwue1ZUxA+wqvWxGDEOB9Yp+L/sQ3JA";
+    const ED25519_HEX: &str = "0000000b7373682d6564323535313900000020c34ab27c81a30e48ad1c2e7b5654c40fb0aaf5b118310e07d629f8bfec437240";
+    // ssh-keygen -t ecdsa -b 256 生成的公钥
+    const ECDSA_BASE64: &str = "AAAAE2VjZHNhLXNoYTItbmlzdHAyNTYAAAAIbmlzdHAyNTYAAABBBA+YDovGF6uxC+3dPRNkdtdrtFKDBIbYqfT/tti0rBB6BXQ5+9QlF6lAnacWmxgPaT1s2U+OVKtUsZDJkwpwMKc=";
+    const ECDSA_HEX: &str = "0000001365636473612d736861322d6e69737470323536000000086e6973747032353600000041040f980e8bc617abb10beddd3d136476d76bb452830486d8a9f4ffb6d8b4ac107a057439fbd42517a9409da7169b180f693d6cd94f8e54ab54b190c9930a7030a7";
+
+    #[test]
+    fn fingerprint_matches_ssh_keygen() {
+        // 期望值来自 ssh-keygen -l -E sha256 -f <公钥文件>
+        let ed25519 = hex::decode(ED25519_HEX).unwrap();
+        assert_eq!(base64(&ed25519), ED25519_BASE64);
+        assert_eq!(fingerprint(&ed25519), "SHA256:UAaEEwTzykrfCY3BeHcuZY9JjSNMRQBI9/64zpdvksg");
+
+        let ecdsa = hex::decode(ECDSA_HEX).unwrap();
+        assert_eq!(base64(&ecdsa), ECDSA_BASE64);
+        assert_eq!(fingerprint(&ecdsa), "SHA256:csTqXzvhjC5PwA65RqLebJ4k6cJWklRqgbHIAJhtlGw");
+    }
+
+    #[test]
+    fn check_key_compares_only_entries_of_the_negotiated_type() {
+        let session = Session::new().unwrap();
+        let ed25519 = hex::decode(ED25519_HEX).unwrap();
+        let ecdsa = hex::decode(ECDSA_HEX).unwrap();
+        let content = format!("# comment\n[example.com]:2222 ssh-ed25519 {}\n", ED25519_BASE64);
+
+        let check = |key: &[u8], key_type| check_key(&session, &content, "example.com", 2222, key, key_type).unwrap();
+        assert!(matches!(check(&ed25519, HostKeyType::Ed25519), CheckResult::Match));
+        // 只记录了 ed25519 时，ecdsa 密钥是未知而不是不匹配
+        assert!(matches!(check(&ecdsa, HostKeyType::Ecdsa256), CheckResult::NotFound));
+        // 同类型的其他密钥仍然是不匹配
+        let mut forged = ed25519.clone();
+        *forged.last_mut().unwrap() ^= 1;
+        assert!(matches!(check(&forged, HostKeyType::Ed25519), CheckResult::Mismatch));
+        // 条目记录的是 2222 端口
+        let other_port = check_key(&session, &content, "example.com", 22, &ed25519, HostKeyType::Ed25519).unwrap();
+        assert!(matches!(other_port, CheckResult::NotFound));
+    }
+
+    #[test]
+    fn recorded_key_types_reads_hashed_entries() {
+        let session = Session::new().unwrap();
+        // ssh-keygen -H 对 [example.com]:2222 条目哈希后的结果
+        let content = format!("|1|0Rho9NFGm1dRDoJkwGhijQak2J4=|g1oCTpiIQ2qzPDYxQe+hV20v9Dc= ssh-ed25519 {}\n", ED25519_BASE64);
+        assert_eq!(recorded_key_types(&session, &content, "example.com", 2222).unwrap(), vec!["ssh-ed25519"]);
+        assert!(recorded_key_types(&session, &content, "example.org", 2222).unwrap().is_empty());
+    }
+}
@@ -1,5 +1,6 @@
 // SSH模块入口
 pub mod client;
+pub mod known_hosts;
 pub mod session;
 
 pub use client::SshClient;
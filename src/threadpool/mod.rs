@@ -52,14 +52,9 @@ struct Worker {
 
 impl Worker {
     fn new(_id: usize, receiver: Arc<Receiver<Job>>) -> Worker {
-        let thread = thread::spawn(move || loop {
-            match receiver.recv() {
-                Ok(job) => {
-                    job();
-                }
-                Err(_) => {
-                    break;
-                }
+        let thread = thread::spawn(move || {
+            while let Ok(job) = receiver.recv() {
+                job();
             }
         });
 
@@ -0,0 +1,119 @@
+// 归档模式：整个目录的文件通过一条 exec 通道以 tar 流传输，省去逐个文件的 SFTP 往返
+use crate::ssh::client::shell_quote;
+use crate::ssh::SshClient;
+use crate::transfer::compression::{Counting, StreamCompression};
+use crate::utils::rate::{Throttle, Throttled};
+use anyhow::{bail, Context, Result};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::sync::atomic::AtomicU64;
+use std::sync::Arc;
+
+// tar 流中的一个条目
+pub enum ArchiveEntry<'a> {
+    // 普通文件的内容
+    File(&'a mut dyn Read),
+    // 与同一归档中较早的文件是同一个 inode，tar 只记录链接目标的名字
+    HardLink(String),
+}
+
+// 在远程把 remote_dir 下的 names 打包为 tar 流，边接收边把每个条目交给 on_entry
+// 文件名列表先经标准输入写入远程临时文件，再启动 tar，避免同时读写通道造成阻塞
+// wire_bytes 累计通道上实际收到的（压缩后的）字节数，throttle 按这些字节限速
+pub fn receive_archive<F>(
+    client: &SshClient,
+    remote_dir: &str,
+    names: &[String],
+    compression: StreamCompression,
+    wire_bytes: Arc<AtomicU64>,
+    throttle: Throttle,
+    mut on_entry: F,
+) -> Result<()>
+where
+    F: FnMut(&str, ArchiveEntry),
+{
+    let tar = format!("tar -c -h -f - -C {} --null -T \"$t\"", shell_quote(remote_dir));
+    let command = format!(
+        "t=$(mktemp) || exit 1; cat > \"$t\"; {}; s=$?; rm -f \"$t\"; exit $s",
+        compression.compress_output(&tar)
+    );
+    let mut channel = client.exec_channel(&command)?;
+    // 未读取的标准错误会占用通道窗口并阻塞 tar 的输出，丢弃它，出错的文件由调用方按缺失处理
+    channel.handle_extended_data(ssh2::ExtendedData::Ignore)?;
+    for name in names {
+        channel.write_all(name.as_bytes())?;
+        channel.write_all(&[0])?;
+    }
+    channel.send_eof()?;
+
+    let mut wire = Counting::new(Throttled::new(&mut channel, throttle), wire_bytes);
+    let mut decoder = compression.decoder(&mut wire)?;
+    let mut archive = tar::Archive::new(&mut decoder);
+    for entry in archive.entries().context("Failed to read tar stream from remote")? {
+        let mut entry = entry.context("Failed to read tar stream from remote")?;
+        let name = entry.path()?.to_string_lossy().to_string();
+        match entry.header().entry_type() {
+            tar::EntryType::Regular | tar::EntryType::Continuous => on_entry(&name, ArchiveEntry::File(&mut entry)),
+            tar::EntryType::Link => {
+                let target = entry.link_name()?
+                    .map(|target| target.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                on_entry(&name, ArchiveEntry::HardLink(target));
+            }
+            other => log::debug!("Ignoring {:?} entry in archive: {}", other, name),
+        }
+    }
+
+    // 读完归档后剩余的输出不再需要，tar 在个别文件读取失败时也会返回非零
+    std::io::copy(&mut decoder, &mut std::io::sink())?;
+    drop(decoder);
+    std::io::copy(&mut wire, &mut std::io::sink())?;
+    finish_tar(&mut channel)
+}
+
+// 在本地构建 tar 流并通过标准输入交给远程 tar 解包到 remote_dir
+// files 为 (本地路径, 归档中的名字)，每发送完一个文件调用一次 on_sent
+// wire_bytes 累计通道上实际发送的（压缩后的）字节数，throttle 按这些字节限速
+pub fn send_archive<'a, I, F>(
+    client: &SshClient,
+    remote_dir: &str,
+    files: I,
+    compression: StreamCompression,
+    wire_bytes: Arc<AtomicU64>,
+    throttle: Throttle,
+    mut on_sent: F,
+) -> Result<()>
+where
+    I: IntoIterator<Item = (&'a Path, String)>,
+    F: FnMut(usize),
+{
+    // -m 不还原修改时间、--no-same-owner 不还原属主，这些属性按 --preserve 处理
+    let tar = format!("tar -x -m --no-same-owner -f - -C {}", shell_quote(remote_dir));
+    let mut channel = client.exec_channel(&compression.decompress_input(&tar))?;
+    {
+        let encoder = compression.encoder(Counting::new(Throttled::new(&mut channel, throttle), wire_bytes))?;
+        let mut builder = tar::Builder::new(encoder);
+        for (index, (local_path, name)) in files.into_iter().enumerate() {
+            builder.append_path_with_name(local_path, &name)
+                .with_context(|| format!("Failed to add {} to the archive", local_path.display()))?;
+            on_sent(index);
+        }
+        builder.into_inner()?.finish()?;
+    }
+    channel.send_eof()?;
+    std::io::copy(&mut channel, &mut std::io::sink())?;
+    finish_tar(&mut channel)
+}
+
+// 等待远程 tar 结束，退出码非零时带上标准错误的最后一行
+fn finish_tar(channel: &mut ssh2::Channel) -> Result<()> {
+    let mut stderr = String::new();
+    let _ = channel.stderr().read_to_string(&mut stderr);
+    channel.wait_close()?;
+    let status = channel.exit_status()?;
+    if status != 0 {
+        bail!("remote tar exited with status {}{}", status,
+              stderr.lines().last().map(|line| format!(": {}", line)).unwrap_or_default());
+    }
+    Ok(())
+}
@@ -0,0 +1,112 @@
+// 传输后保留文件属性：权限位、访问/修改时间和属主
+use anyhow::Result;
+use filetime::FileTime;
+use ssh2::{FileStat, Sftp};
+use std::fs::Metadata;
+use std::path::Path;
+
+// 需要应用到目标文件的属性
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AttributeSet {
+    pub mode: bool,
+    pub times: bool,
+    pub owner: bool,
+}
+
+impl AttributeSet {
+    pub fn is_empty(&self) -> bool {
+        !(self.mode || self.times || self.owner)
+    }
+}
+
+// 源文件的属性
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FileAttributes {
+    pub mode: Option<u32>,
+    pub atime: u64,
+    pub mtime: u64,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+}
+
+impl FileAttributes {
+    pub fn from_remote(stat: &FileStat) -> Self {
+        let mtime = stat.mtime.unwrap_or(0);
+        Self {
+            mode: stat.perm.map(|perm| perm & 0o7777),
+            atime: stat.atime.unwrap_or(mtime),
+            mtime,
+            uid: stat.uid,
+            gid: stat.gid,
+        }
+    }
+
+    pub fn from_local(metadata: &Metadata) -> Self {
+        let mtime = crate::utils::file::mtime_secs(metadata);
+        let atime = metadata
+            .accessed()
+            .ok()
+            .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|duration| duration.as_secs())
+            .unwrap_or(mtime);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::{MetadataExt, PermissionsExt};
+            Self {
+                mode: Some(metadata.permissions().mode() & 0o7777),
+                atime,
+                mtime,
+                uid: Some(metadata.uid()),
+                gid: Some(metadata.gid()),
+            }
+        }
+        #[cfg(not(unix))]
+        {
+            Self { mode: None, atime, mtime, uid: None, gid: None }
+        }
+    }
+
+    // 应用到本地文件或目录；修改属主会清除 setuid 位，所以先于权限位设置
+    // 没有权限修改属主时只给出警告
+    pub fn apply_local(&self, path: &Path, which: AttributeSet) -> Result<()> {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            if which.owner {
+                if let Err(e) = std::os::unix::fs::chown(path, self.uid, self.gid) {
+                    log::warn!("Could not change owner of {}: {}", path.display(), e);
+                }
+            }
+            if let (true, Some(mode)) = (which.mode, self.mode) {
+                std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))?;
+            }
+        }
+        if which.times {
+            filetime::set_file_times(
+                path,
+                FileTime::from_unix_time(self.atime as i64, 0),
+                FileTime::from_unix_time(self.mtime as i64, 0),
+            )?;
+        }
+        Ok(())
+    }
+
+    // 通过 SFTP setstat 应用到远程文件或目录
+    pub fn apply_remote(&self, sftp: &Sftp, path: &str, which: AttributeSet) -> Result<()> {
+        if let (true, Some(uid), Some(gid)) = (which.owner, self.uid, self.gid) {
+            let stat = FileStat { size: None, uid: Some(uid), gid: Some(gid), perm: None, atime: None, mtime: None };
+            if let Err(e) = sftp.setstat(Path::new(path), stat) {
+                log::warn!("Could not change owner of {}: {}", path, e);
+            }
+        }
+
+        let perm = if which.mode { self.mode } else { None };
+        let (atime, mtime) = if which.times { (Some(self.atime), Some(self.mtime)) } else { (None, None) };
+        if perm.is_some() || mtime.is_some() {
+            let stat = FileStat { size: None, uid: None, gid: None, perm, atime, mtime };
+            sftp.setstat(Path::new(path), stat)?;
+        }
+        Ok(())
+    }
+}
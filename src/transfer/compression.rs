@@ -0,0 +1,138 @@
+// 基于 exec 通道的传输流压缩，并统计压缩后实际经过网络的字节数
+use anyhow::Result;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use std::io::{self, Read, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum StreamCompression {
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl StreamCompression {
+    fn remote_compress(&self) -> Option<&'static str> {
+        match self {
+            StreamCompression::None => None,
+            StreamCompression::Gzip => Some("gzip -c"),
+            StreamCompression::Zstd => Some("zstd -c -q"),
+        }
+    }
+
+    fn remote_decompress(&self) -> Option<&'static str> {
+        match self {
+            StreamCompression::None => None,
+            StreamCompression::Gzip => Some("gzip -dc"),
+            StreamCompression::Zstd => Some("zstd -dc -q"),
+        }
+    }
+
+    // 远程命令的输出经压缩后发送；执行完 $? 仍是原命令的退出码，而不是压缩程序的
+    pub fn compress_output(&self, command: &str) -> String {
+        match self.remote_compress() {
+            None => command.to_string(),
+            Some(compress) => format!(
+                "z=$(mktemp) || exit 1; {{ {}; echo $? > \"$z\"; }} | {}; c=$(cat \"$z\"); rm -f \"$z\"; (exit ${{c:-1}})",
+                command, compress
+            ),
+        }
+    }
+
+    // 远程命令的输入先解压
+    pub fn decompress_input(&self, command: &str) -> String {
+        match self.remote_decompress() {
+            None => command.to_string(),
+            Some(decompress) => format!("{} | {}", decompress, command),
+        }
+    }
+
+    pub fn decoder<'a, R: Read + 'a>(&self, reader: R) -> Result<Box<dyn Read + 'a>> {
+        Ok(match self {
+            StreamCompression::None => Box::new(reader),
+            StreamCompression::Gzip => Box::new(GzDecoder::new(reader)),
+            StreamCompression::Zstd => Box::new(zstd::Decoder::new(reader)?),
+        })
+    }
+
+    pub fn encoder<W: Write>(&self, writer: W) -> Result<StreamEncoder<W>> {
+        Ok(match self {
+            StreamCompression::None => StreamEncoder::Plain(writer),
+            StreamCompression::Gzip => StreamEncoder::Gzip(GzEncoder::new(writer, flate2::Compression::default())),
+            StreamCompression::Zstd => StreamEncoder::Zstd(zstd::Encoder::new(writer, 0)?),
+        })
+    }
+}
+
+// 压缩写入端，finish 写出压缩格式的结尾并返回底层写入端
+pub enum StreamEncoder<W: Write> {
+    Plain(W),
+    Gzip(GzEncoder<W>),
+    Zstd(zstd::Encoder<'static, W>),
+}
+
+impl<W: Write> StreamEncoder<W> {
+    pub fn finish(self) -> io::Result<W> {
+        match self {
+            StreamEncoder::Plain(mut writer) => {
+                writer.flush()?;
+                Ok(writer)
+            }
+            StreamEncoder::Gzip(encoder) => encoder.finish(),
+            StreamEncoder::Zstd(encoder) => encoder.finish(),
+        }
+    }
+}
+
+impl<W: Write> Write for StreamEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            StreamEncoder::Plain(writer) => writer.write(buf),
+            StreamEncoder::Gzip(encoder) => encoder.write(buf),
+            StreamEncoder::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            StreamEncoder::Plain(writer) => writer.flush(),
+            StreamEncoder::Gzip(encoder) => encoder.flush(),
+            StreamEncoder::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+// 统计经过的字节数，包在压缩流和通道之间得到网络上的字节数
+pub struct Counting<T> {
+    inner: T,
+    count: Arc<AtomicU64>,
+}
+
+impl<T> Counting<T> {
+    pub fn new(inner: T, count: Arc<AtomicU64>) -> Self {
+        Self { inner, count }
+    }
+}
+
+impl<R: Read> Read for Counting<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count.fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
+}
+
+impl<W: Write> Write for Counting<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.count.fetch_add(n as u64, Ordering::Relaxed);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,165 @@
+// 目标文件已存在时的处理策略
+use anyhow::Result;
+use dialoguer::Select;
+use std::path::Path;
+use std::sync::Mutex;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum ConflictPolicy {
+    // 总是覆盖
+    #[default]
+    Overwrite,
+    // 跳过已存在的文件
+    Skip,
+    // 源文件修改时间更新时才覆盖
+    Newer,
+    // 大小或摘要不同时才覆盖
+    Differs,
+    // 写入 "name (1).ext" 这样的新文件名
+    Rename,
+    // 交互式询问
+    Prompt,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    Overwrite,
+    Skip,
+    Rename,
+}
+
+// 参与比较的文件属性
+#[derive(Debug, Clone, Copy)]
+pub struct FileFacts {
+    pub size: u64,
+    pub mtime: u64,
+}
+
+pub struct ConflictResolver {
+    policy: ConflictPolicy,
+    // 交互模式下选择了 "全部" 后记住的决定
+    remembered: Mutex<Option<Resolution>>,
+}
+
+impl ConflictResolver {
+    pub fn new(policy: ConflictPolicy) -> Self {
+        Self {
+            policy,
+            remembered: Mutex::new(None),
+        }
+    }
+
+    // 是否需要交互式询问用户
+    pub fn is_interactive(&self) -> bool {
+        self.policy == ConflictPolicy::Prompt
+    }
+
+    // 决定如何处理已存在的目标文件，contents_differ 只在需要比较摘要时调用
+    pub fn resolve<F>(&self, target: &str, source: FileFacts, existing: FileFacts, contents_differ: F) -> Result<Resolution>
+    where
+        F: FnOnce() -> Result<bool>,
+    {
+        let resolution = match self.policy {
+            ConflictPolicy::Overwrite => Resolution::Overwrite,
+            ConflictPolicy::Skip => Resolution::Skip,
+            ConflictPolicy::Rename => Resolution::Rename,
+            ConflictPolicy::Newer => {
+                if source.mtime > existing.mtime {
+                    Resolution::Overwrite
+                } else {
+                    Resolution::Skip
+                }
+            }
+            ConflictPolicy::Differs => {
+                if source.size != existing.size || contents_differ()? {
+                    Resolution::Overwrite
+                } else {
+                    Resolution::Skip
+                }
+            }
+            ConflictPolicy::Prompt => self.prompt(target, source, existing)?,
+        };
+        Ok(resolution)
+    }
+
+    fn prompt(&self, target: &str, source: FileFacts, existing: FileFacts) -> Result<Resolution> {
+        let mut remembered = self.remembered.lock().unwrap();
+        if let Some(resolution) = *remembered {
+            return Ok(resolution);
+        }
+
+        let newer = if source.mtime > existing.mtime { "source is newer" } else { "target is newer or same age" };
+        let choice = Select::new()
+            .with_prompt(format!(
+                "{} already exists (source {} bytes, target {} bytes, {})",
+                target, source.size, existing.size, newer
+            ))
+            .items(&["Overwrite", "Skip", "Rename", "Overwrite all", "Skip all", "Rename all"])
+            .default(0)
+            .interact()?;
+
+        let resolution = match choice % 3 {
+            0 => Resolution::Overwrite,
+            1 => Resolution::Skip,
+            _ => Resolution::Rename,
+        };
+        if choice >= 3 {
+            *remembered = Some(resolution);
+        }
+        Ok(resolution)
+    }
+}
+
+// 生成第 n 个候选文件名，例如 "report.txt" -> "report (1).txt"
+pub fn numbered_name(path: &Path, n: usize) -> String {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let file_name = match path.extension() {
+        Some(ext) => format!("{} ({}).{}", stem, n, ext.to_string_lossy()),
+        None => format!("{} ({})", stem, n),
+    };
+    path.with_file_name(file_name).to_string_lossy().to_string()
+}
+
+// 找到第一个不存在的编号文件名
+pub fn free_name<F>(path: &Path, exists: F) -> String
+where
+    F: Fn(&str) -> bool,
+{
+    let mut n = 1;
+    loop {
+        let candidate = numbered_name(path, n);
+        if !exists(&candidate) {
+            return candidate;
+        }
+        n += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn numbered_name_inserts_number_before_extension() {
+        assert_eq!(numbered_name(Path::new("report.txt"), 1), "report (1).txt");
+        assert_eq!(numbered_name(Path::new("/remote/dir/report.txt"), 3), "/remote/dir/report (3).txt");
+        assert_eq!(numbered_name(Path::new("Makefile"), 1), "Makefile (1)");
+        // 点开头的文件没有扩展名，编号加在末尾
+        assert_eq!(numbered_name(Path::new("dir/.bashrc"), 1), "dir/.bashrc (1)");
+        // 多个点的文件名只把最后一段当作扩展名
+        assert_eq!(numbered_name(Path::new("a.tar.gz"), 2), "a.tar (2).gz");
+    }
+
+    #[test]
+    fn free_name_skips_existing_candidates() {
+        let path = Path::new("/data/a.tar.gz");
+        assert_eq!(free_name(path, |_| false), "/data/a.tar (1).gz");
+
+        let existing: HashSet<&str> = ["/data/a.tar (1).gz", "/data/a.tar (2).gz"].into_iter().collect();
+        assert_eq!(free_name(path, |candidate| existing.contains(candidate)), "/data/a.tar (3).gz");
+
+        let existing: HashSet<&str> = ["notes (1)"].into_iter().collect();
+        assert_eq!(free_name(Path::new("notes"), |candidate| existing.contains(candidate)), "notes (2)");
+    }
+}
@@ -0,0 +1,663 @@
+// 基于滚动校验和的增量传输：只发送目标端没有的数据
+use crate::ssh::client::shell_quote;
+use crate::ssh::SshClient;
+use crate::utils::rate::{Throttle, Throttled};
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+// Adler-32 的模数，与 zlib.adler32 保持一致
+const MOD_ADLER: u32 = 65521;
+// 单个字面数据操作的最大长度
+const MAX_LITERAL: usize = 1024 * 1024;
+
+// 远程辅助脚本，负责在目标端计算块签名、生成增量和重建文件
+const HELPER: &str = r#"
+import sys, os, zlib, hashlib, struct, mmap
+M = 65521
+def load(path):
+    f = open(path, "rb")
+    if os.path.getsize(path) == 0:
+        return b""
+    return mmap.mmap(f.fileno(), 0, access=mmap.ACCESS_READ)
+def read_exact(f, n):
+    buf = b""
+    while len(buf) < n:
+        chunk = f.read(n - len(buf))
+        if not chunk:
+            sys.exit(4)
+        buf += chunk
+    return buf
+def sig(path, bs):
+    out = sys.stdout
+    out.write("%d %d\n" % (bs, os.path.getsize(path)))
+    with open(path, "rb") as f:
+        while True:
+            b = f.read(bs)
+            if not b:
+                break
+            out.write("%08x %s\n" % (zlib.adler32(b) & 0xffffffff, hashlib.md5(b).hexdigest()))
+def delta(path):
+    inp = sys.stdin
+    head = inp.readline().split()
+    bs, size = int(head[0]), int(head[1])
+    table = {}
+    for i, line in enumerate(inp):
+        w, s = line.split()
+        table.setdefault(int(w, 16), {}).setdefault(s, i)
+    out = sys.stdout.buffer
+    data = load(path)
+    n = len(data)
+    pos = lit = 0
+    budget = n // 2 + (64 << 20)
+    a = b = None
+    def find(block):
+        c = table.get(zlib.adler32(block) & 0xffffffff)
+        return c.get(hashlib.md5(block).hexdigest()) if c else None
+    def flush(start, end):
+        while start < end:
+            e = min(end, start + (1 << 20))
+            out.write(b"L" + struct.pack(">I", e - start))
+            out.write(data[start:e])
+            start = e
+    while pos + bs <= n and budget > 0:
+        if a is None:
+            w = zlib.adler32(data[pos:pos + bs])
+            a, b = w & 0xffff, w >> 16
+        c = table.get((b << 16) | a)
+        if c:
+            i = c.get(hashlib.md5(data[pos:pos + bs]).hexdigest())
+            if i is not None:
+                flush(lit, pos)
+                out.write(b"C" + struct.pack(">Q", i))
+                pos += bs
+                lit = pos
+                a = None
+                continue
+        if pos + bs == n:
+            break
+        x, y = data[pos], data[pos + bs]
+        a = (a - x + y) % M
+        b = (b - bs * x + a - 1) % M
+        pos += 1
+        budget -= 1
+        if pos - lit >= (1 << 20):
+            flush(lit, pos)
+            lit = pos
+    tail = size % bs
+    if tail and n - tail >= lit:
+        i = find(data[n - tail:n])
+        if i is not None:
+            flush(lit, n - tail)
+            out.write(b"C" + struct.pack(">Q", i))
+            lit = n
+    flush(lit, n)
+    out.write(b"E" + hashlib.md5(data).hexdigest().encode())
+    out.flush()
+def patch(old, part, bs):
+    inp = sys.stdin.buffer
+    h = hashlib.md5()
+    with open(old, "rb") as src, open(part, "wb") as dst:
+        while True:
+            op = inp.read(1)
+            if op == b"C":
+                i, = struct.unpack(">Q", read_exact(inp, 8))
+                src.seek(i * bs)
+                block = src.read(bs)
+            elif op == b"L":
+                n, = struct.unpack(">I", read_exact(inp, 4))
+                block = read_exact(inp, n)
+            elif op == b"E":
+                break
+            else:
+                sys.exit(3)
+            dst.write(block)
+            h.update(block)
+    sys.stdout.write(h.hexdigest() + "\n")
+mode = sys.argv[1]
+if mode == "sig":
+    sig(sys.argv[2], int(sys.argv[3]))
+elif mode == "delta":
+    delta(sys.argv[2])
+elif mode == "patch":
+    patch(sys.argv[2], sys.argv[3], int(sys.argv[4]))
+"#;
+
+// 目标文件一个块的弱校验和与强校验和
+#[derive(Debug, Clone)]
+pub struct BlockSignature {
+    pub weak: u32,
+    pub strong: [u8; 16],
+}
+
+// 目标文件的块签名
+#[derive(Debug, Clone)]
+pub struct Signature {
+    pub block_size: usize,
+    pub file_size: u64,
+    pub blocks: Vec<BlockSignature>,
+}
+
+// 增量操作：复制目标端已有的块，或者发送字面数据
+#[derive(Debug)]
+pub enum DeltaOp {
+    Copy(u64),
+    Literal(Vec<u8>),
+}
+
+// 增量传输的统计
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DeltaStats {
+    pub literal_bytes: u64,
+    pub matched_bytes: u64,
+}
+
+impl DeltaStats {
+    // 相对完整传输节省的比例
+    pub fn saved_percent(&self) -> f64 {
+        let total = self.literal_bytes + self.matched_bytes;
+        if total == 0 {
+            return 0.0;
+        }
+        self.matched_bytes as f64 * 100.0 / total as f64
+    }
+}
+
+// 根据文件大小选择块大小，与 rsync 一样取平方根附近的值
+pub fn block_size_for(size: u64) -> usize {
+    let root = (size as f64).sqrt() as usize;
+    (root / 8 * 8).clamp(2048, 128 * 1024)
+}
+
+// 可滚动的 Adler-32 校验和
+struct Rolling {
+    a: u32,
+    b: u32,
+    len: u32,
+}
+
+impl Rolling {
+    fn new(block: &[u8]) -> Self {
+        let mut a = 1u32;
+        let mut b = 0u32;
+        for &byte in block {
+            a = (a + byte as u32) % MOD_ADLER;
+            b = (b + a) % MOD_ADLER;
+        }
+        Self { a, b, len: block.len() as u32 }
+    }
+
+    fn digest(&self) -> u32 {
+        (self.b << 16) | self.a
+    }
+
+    // 窗口向后滑动一个字节：移出 out，移入 byte
+    fn roll(&mut self, out: u8, byte: u8) {
+        let removed = (self.len as u64 * out as u64 % MOD_ADLER as u64) as u32;
+        self.a = (self.a + MOD_ADLER - out as u32 + byte as u32) % MOD_ADLER;
+        self.b = (self.b + 2 * MOD_ADLER - removed + self.a - 1) % MOD_ADLER;
+    }
+}
+
+fn weak_checksum(block: &[u8]) -> u32 {
+    Rolling::new(block).digest()
+}
+
+impl Signature {
+    // 读取本地文件计算块签名
+    pub fn compute<R: Read>(mut reader: R, file_size: u64, block_size: usize) -> Result<Self> {
+        let mut blocks = Vec::new();
+        let mut buffer = vec![0u8; block_size];
+        loop {
+            let n = read_full(&mut reader, &mut buffer)?;
+            if n == 0 {
+                break;
+            }
+            blocks.push(BlockSignature {
+                weak: weak_checksum(&buffer[..n]),
+                strong: md5::compute(&buffer[..n]).0,
+            });
+            if n < block_size {
+                break;
+            }
+        }
+        Ok(Self { block_size, file_size, blocks })
+    }
+
+    // 解析辅助脚本输出的文本签名
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut lines = text.lines();
+        let header = lines.next().ok_or_else(|| anyhow::anyhow!("Empty block signature"))?;
+        let mut fields = header.split_whitespace();
+        let block_size: usize = fields.next().unwrap_or_default().parse().context("Invalid block signature header")?;
+        let file_size: u64 = fields.next().unwrap_or_default().parse().context("Invalid block signature header")?;
+
+        let mut blocks = Vec::new();
+        for line in lines {
+            let (weak, strong) = line.split_once(' ')
+                .ok_or_else(|| anyhow::anyhow!("Invalid block signature line: {}", line))?;
+            let strong = hex::decode(strong.trim())?;
+            blocks.push(BlockSignature {
+                weak: u32::from_str_radix(weak, 16)?,
+                strong: strong.try_into().map_err(|_| anyhow::anyhow!("Invalid block checksum: {}", line))?,
+            });
+        }
+        if block_size == 0 || blocks.len() as u64 != file_size.div_ceil(block_size as u64) {
+            return Err(anyhow::anyhow!("Incomplete block signature"));
+        }
+        Ok(Self { block_size, file_size, blocks })
+    }
+
+    // 序列化为辅助脚本使用的文本格式
+    pub fn to_text(&self) -> String {
+        let mut text = format!("{} {}\n", self.block_size, self.file_size);
+        for block in &self.blocks {
+            text.push_str(&format!("{:08x} {}\n", block.weak, hex::encode(block.strong)));
+        }
+        text
+    }
+
+    // 以弱校验和为键建立索引，同一弱校验和可能对应多个块
+    fn index(&self) -> HashMap<u32, Vec<usize>> {
+        let mut table: HashMap<u32, Vec<usize>> = HashMap::new();
+        for (i, block) in self.blocks.iter().enumerate() {
+            table.entry(block.weak).or_default().push(i);
+        }
+        table
+    }
+
+    fn find(&self, table: &HashMap<u32, Vec<usize>>, weak: u32, data: &[u8]) -> Option<usize> {
+        let candidates = table.get(&weak)?;
+        let strong = md5::compute(data).0;
+        candidates.iter().copied().find(|&i| self.blocks[i].strong == strong)
+    }
+}
+
+// 尽量读满缓冲区，返回实际读取的字节数
+fn read_full<R: Read>(reader: &mut R, buffer: &mut [u8]) -> Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
+
+// 在源数据中查找目标端已有的块，生成增量操作，返回统计和源数据的 MD5
+pub fn compute_delta<R, F>(mut source: R, signature: &Signature, mut emit: F) -> Result<(DeltaStats, String)>
+where
+    R: Read,
+    F: FnMut(DeltaOp) -> Result<()>,
+{
+    let block_size = signature.block_size;
+    let table = signature.index();
+    let mut stats = DeltaStats::default();
+    let mut hasher = md5::Context::new();
+
+    let mut data: Vec<u8> = Vec::new();
+    let mut pos = 0;
+    let mut literal_start = 0;
+    let mut eof = false;
+    let mut rolling: Option<Rolling> = None;
+    let mut read_buffer = vec![0u8; MAX_LITERAL.max(block_size * 4)];
+
+    let flush = |data: &[u8], stats: &mut DeltaStats, emit: &mut F| -> Result<()> {
+        for piece in data.chunks(MAX_LITERAL) {
+            stats.literal_bytes += piece.len() as u64;
+            emit(DeltaOp::Literal(piece.to_vec()))?;
+        }
+        Ok(())
+    };
+
+    loop {
+        // 窗口之后至少保留一个字节用于滚动，不足时丢弃已处理的数据并继续读取
+        if !eof && data.len() - pos <= block_size {
+            flush(&data[literal_start..pos], &mut stats, &mut emit)?;
+            data.drain(..pos);
+            pos = 0;
+            literal_start = 0;
+            let n = source.read(&mut read_buffer)?;
+            if n == 0 {
+                eof = true;
+            } else {
+                hasher.consume(&read_buffer[..n]);
+                data.extend_from_slice(&read_buffer[..n]);
+                continue;
+            }
+        }
+
+        if data.len() - pos < block_size {
+            break;
+        }
+
+        let window = &data[pos..pos + block_size];
+        let weak = rolling.get_or_insert_with(|| Rolling::new(window)).digest();
+        if let Some(index) = signature.find(&table, weak, window) {
+            flush(&data[literal_start..pos], &mut stats, &mut emit)?;
+            emit(DeltaOp::Copy(index as u64))?;
+            stats.matched_bytes += block_size as u64;
+            pos += block_size;
+            literal_start = pos;
+            rolling = None;
+            continue;
+        }
+
+        if pos + block_size == data.len() {
+            break;
+        }
+        if let Some(rolling) = rolling.as_mut() {
+            rolling.roll(data[pos], data[pos + block_size]);
+        }
+        pos += 1;
+        if pos - literal_start >= MAX_LITERAL {
+            flush(&data[literal_start..pos], &mut stats, &mut emit)?;
+            literal_start = pos;
+        }
+    }
+
+    // 目标文件最后一个不完整的块只在源数据末尾匹配
+    let tail = (signature.file_size % block_size as u64) as usize;
+    if tail > 0 && data.len() >= tail && data.len() - tail >= literal_start {
+        let start = data.len() - tail;
+        let block = &data[start..];
+        if let Some(index) = signature.find(&table, weak_checksum(block), block) {
+            flush(&data[literal_start..start], &mut stats, &mut emit)?;
+            emit(DeltaOp::Copy(index as u64))?;
+            stats.matched_bytes += tail as u64;
+            literal_start = data.len();
+        }
+    }
+    flush(&data[literal_start..], &mut stats, &mut emit)?;
+
+    Ok((stats, format!("{:x}", hasher.compute())))
+}
+
+// 按辅助脚本的二进制格式写出一个增量操作
+fn write_op<W: Write>(writer: &mut W, op: &DeltaOp) -> Result<()> {
+    match op {
+        DeltaOp::Copy(index) => {
+            writer.write_all(b"C")?;
+            writer.write_all(&index.to_be_bytes())?;
+        }
+        DeltaOp::Literal(data) => {
+            writer.write_all(b"L")?;
+            writer.write_all(&(data.len() as u32).to_be_bytes())?;
+            writer.write_all(data)?;
+        }
+    }
+    Ok(())
+}
+
+// 用本地旧文件和增量操作流重建文件，返回统计、源文件 MD5 和重建结果的 MD5
+// 每写出一段数据用写出的字节数调用一次 on_progress
+pub fn apply_delta<R, W, F>(mut ops: R, basis: &mut File, output: &mut W, block_size: usize, mut on_progress: F) -> Result<(DeltaStats, String, String)>
+where
+    R: Read,
+    W: Write,
+    F: FnMut(u64),
+{
+    let mut stats = DeltaStats::default();
+    let mut hasher = md5::Context::new();
+    let mut buffer = vec![0u8; block_size.max(MAX_LITERAL)];
+
+    loop {
+        let mut tag = [0u8; 1];
+        ops.read_exact(&mut tag).context("Delta stream ended unexpectedly")?;
+        match &tag {
+            b"C" => {
+                let mut index = [0u8; 8];
+                ops.read_exact(&mut index)?;
+                basis.seek(SeekFrom::Start(u64::from_be_bytes(index) * block_size as u64))?;
+                let n = read_full(basis, &mut buffer[..block_size])?;
+                output.write_all(&buffer[..n])?;
+                hasher.consume(&buffer[..n]);
+                stats.matched_bytes += n as u64;
+                on_progress(n as u64);
+            }
+            b"L" => {
+                let mut len = [0u8; 4];
+                ops.read_exact(&mut len)?;
+                let len = u32::from_be_bytes(len) as usize;
+                if len > buffer.len() {
+                    return Err(anyhow::anyhow!("Literal block of {} bytes exceeds the protocol limit", len));
+                }
+                ops.read_exact(&mut buffer[..len])?;
+                output.write_all(&buffer[..len])?;
+                hasher.consume(&buffer[..len]);
+                stats.literal_bytes += len as u64;
+                on_progress(len as u64);
+            }
+            b"E" => {
+                let mut expected = [0u8; 32];
+                ops.read_exact(&mut expected)?;
+                let expected = String::from_utf8_lossy(&expected).to_string();
+                return Ok((stats, expected, format!("{:x}", hasher.compute())));
+            }
+            _ => return Err(anyhow::anyhow!("Invalid delta operation: {:?}", tag)),
+        }
+    }
+}
+
+// 构造调用远程辅助脚本的命令
+fn helper_command(args: &[&str]) -> String {
+    let args: Vec<String> = args.iter().map(|arg| shell_quote(arg)).collect();
+    format!("python3 -c {} {}", shell_quote(HELPER), args.join(" "))
+}
+
+// 等待远程命令结束，退出码非零时说明辅助脚本无法运行
+fn finish_channel(channel: &mut ssh2::Channel) -> Result<()> {
+    channel.wait_close()?;
+    let status = channel.exit_status()?;
+    if status != 0 {
+        let mut stderr = String::new();
+        let _ = channel.stderr().read_to_string(&mut stderr);
+        return Err(anyhow::anyhow!(
+            "remote delta helper exited with status {} (python3 is required on the remote host){}",
+            status,
+            stderr.lines().last().map(|line| format!(": {}", line)).unwrap_or_default()
+        ));
+    }
+    Ok(())
+}
+
+// 增量上传：在远程计算旧文件的块签名，本地只发送变化的数据，远程重建到 remote_part
+// 通道上的数据按 throttle 限速，每处理一段源数据用其字节数调用一次 on_progress
+pub fn upload_delta<F>(
+    client: &SshClient,
+    local_path: &Path,
+    remote_old: &str,
+    remote_part: &str,
+    remote_size: u64,
+    throttle: Throttle,
+    mut on_progress: F,
+) -> Result<DeltaStats>
+where
+    F: FnMut(u64),
+{
+    let block_size = block_size_for(remote_size);
+
+    let mut channel = client.exec_channel(&helper_command(&["sig", remote_old, &block_size.to_string()]))?;
+    let mut text = String::new();
+    Throttled::new(&mut channel, throttle.clone()).read_to_string(&mut text)?;
+    finish_channel(&mut channel)?;
+    let signature = Signature::parse(&text)?;
+
+    let mut channel = client.exec_channel(&helper_command(&["patch", remote_old, remote_part, &block_size.to_string()]))?;
+    let source = BufReader::new(File::open(local_path)?);
+    let (stats, local_md5) = {
+        let mut writer = BufWriter::new(Throttled::new(&mut channel, throttle));
+        let result = compute_delta(source, &signature, |op| {
+            // 复制的块在远程旧文件末尾时可能不足一个块
+            let len = match &op {
+                DeltaOp::Copy(index) => remote_size.saturating_sub(index * block_size as u64).min(block_size as u64),
+                DeltaOp::Literal(data) => data.len() as u64,
+            };
+            write_op(&mut writer, &op)?;
+            on_progress(len);
+            Ok(())
+        })?;
+        writer.write_all(b"E")?;
+        writer.flush()?;
+        result
+    };
+    channel.send_eof()?;
+    let mut output = String::new();
+    channel.read_to_string(&mut output)?;
+    finish_channel(&mut channel)?;
+
+    if output.trim() != local_md5 {
+        return Err(anyhow::anyhow!("Delta reconstruction of {} does not match the source", remote_old));
+    }
+    Ok(stats)
+}
+
+// 增量下载：本地计算旧文件的块签名，远程生成增量，本地重建到 local_part
+// 通道上的数据按 throttle 限速，每重建一段数据用其字节数调用一次 on_progress
+pub fn download_delta<F>(
+    client: &SshClient,
+    remote_path: &str,
+    local_old: &Path,
+    local_part: &Path,
+    throttle: Throttle,
+    on_progress: F,
+) -> Result<DeltaStats>
+where
+    F: FnMut(u64),
+{
+    let mut basis = File::open(local_old)?;
+    let local_size = basis.metadata()?.len();
+    let signature = Signature::compute(BufReader::new(&mut basis), local_size, block_size_for(local_size))?;
+
+    let mut channel = client.exec_channel(&helper_command(&["delta", remote_path]))?;
+    Throttled::new(&mut channel, throttle.clone()).write_all(signature.to_text().as_bytes())?;
+    channel.send_eof()?;
+
+    crate::utils::file::ensure_parent_dir(local_part)?;
+    let mut output = BufWriter::new(File::create(local_part)?);
+    let ops = BufReader::new(Throttled::new(&mut channel, throttle));
+    let result = apply_delta(ops, &mut basis, &mut output, signature.block_size, on_progress);
+    // 辅助脚本无法运行时先报告退出状态
+    let (stats, expected, actual) = match result {
+        Ok(result) => result,
+        Err(e) => {
+            finish_channel(&mut channel)?;
+            return Err(e);
+        }
+    };
+    output.flush()?;
+    finish_channel(&mut channel)?;
+
+    if expected != actual {
+        return Err(anyhow::anyhow!("Delta reconstruction of {} does not match the source", remote_path));
+    }
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLOCK: usize = 64;
+
+    // 确定性的伪随机数据，块之间互不相同
+    fn sample(len: usize, seed: u32) -> Vec<u8> {
+        let mut x = seed;
+        (0..len).map(|_| {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            (x >> 16) as u8
+        }).collect()
+    }
+
+    // 按定义逐字节计算的 Adler-32
+    fn adler32(data: &[u8]) -> u32 {
+        let (mut a, mut b) = (1u32, 0u32);
+        for &byte in data {
+            a = (a + byte as u32) % MOD_ADLER;
+            b = (b + a) % MOD_ADLER;
+        }
+        (b << 16) | a
+    }
+
+    #[test]
+    fn rolling_checksum_matches_adler32_of_every_window() {
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+        // 全 0xff 的数据让移出项最大，检查取模不会溢出
+        let mut data = sample(6000, 7);
+        data.extend(std::iter::repeat_n(0xff, 3000));
+        for window in [1, 17, BLOCK, 2048] {
+            let mut rolling = Rolling::new(&data[..window]);
+            for start in 0..data.len() - window {
+                assert_eq!(rolling.digest(), adler32(&data[start..start + window]), "window {} at {}", window, start);
+                rolling.roll(data[start], data[start + window]);
+            }
+        }
+    }
+
+    // 生成增量后在旧文件上重建，检查结果与新数据一致
+    fn round_trip(name: &str, old: &[u8], new: &[u8]) -> DeltaStats {
+        let path = std::env::temp_dir().join(format!("ssh-transfer-delta-{}-{}", std::process::id(), name));
+        std::fs::write(&path, old).unwrap();
+        let signature = Signature::compute(old, old.len() as u64, BLOCK).unwrap();
+
+        let mut ops = Vec::new();
+        let (sent, md5) = compute_delta(new, &signature, |op| write_op(&mut ops, &op)).unwrap();
+        ops.push(b'E');
+        ops.extend_from_slice(md5.as_bytes());
+
+        let mut basis = File::open(&path).unwrap();
+        let mut output = Vec::new();
+        let mut reported = 0;
+        let (applied, expected, actual) = apply_delta(&ops[..], &mut basis, &mut output, BLOCK, |n| reported += n).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(output == new, "{}: reconstructed data differs", name);
+        assert_eq!(expected, actual);
+        assert_eq!(reported, new.len() as u64);
+        assert_eq!((sent.literal_bytes, sent.matched_bytes), (applied.literal_bytes, applied.matched_bytes));
+        sent
+    }
+
+    #[test]
+    fn delta_round_trip_after_insert_delete_and_shift() {
+        // 长度不是块大小的整数倍，覆盖末尾不完整块的匹配
+        let old = sample(4100, 1);
+
+        let unchanged = round_trip("same", &old, &old);
+        assert_eq!(unchanged.literal_bytes, 0);
+
+        let mut inserted = old.clone();
+        inserted.splice(1000..1000, b"inserted bytes".iter().copied());
+        let stats = round_trip("insert", &old, &inserted);
+        assert!(stats.literal_bytes < 2 * BLOCK as u64 + 14, "{:?}", stats);
+
+        let mut deleted = old.clone();
+        deleted.drain(1000..1100);
+        let stats = round_trip("delete", &old, &deleted);
+        assert!(stats.literal_bytes < 2 * BLOCK as u64, "{:?}", stats);
+
+        let mut shifted = b"shift".to_vec();
+        shifted.extend_from_slice(&old);
+        let stats = round_trip("shift", &old, &shifted);
+        assert!(stats.literal_bytes < BLOCK as u64, "{:?}", stats);
+
+        round_trip("unrelated", &old, &sample(3000, 2));
+        round_trip("empty", &old, &[]);
+    }
+
+    #[test]
+    fn signature_matches_python_helper() {
+        // 期望值为远程辅助脚本对同一数据的输出：python3 -c "$HELPER" sig fixture.bin 2048
+        const PYTHON_SIG: &str = "2048 5000\n\
+            53c1c501 ceb1bbe88f0e23323e627424d218faf9\n\
+            c014c8b5 a4087a1ca600926b92a38541a21b7147\n\
+            13a2adcb 74651744fab81197641dd2e9ac9cfced\n";
+        let data: Vec<u8> = (0..5000u64).map(|i| ((i * i * 31 + i * 7 + 3) % 251) as u8).collect();
+        let signature = Signature::compute(&data[..], data.len() as u64, 2048).unwrap();
+        assert_eq!(signature.to_text(), PYTHON_SIG);
+        assert_eq!(Signature::parse(PYTHON_SIG).unwrap().to_text(), PYTHON_SIG);
+    }
+}
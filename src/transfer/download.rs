@@ -1,15 +1,31 @@
 // 下载功能
 use crate::config::Config;
-use crate::ssh::SshSession;
+use crate::ssh::{SshClient, SshSession};
+use crate::transfer::archive::{self, ArchiveEntry};
+use crate::transfer::delta::{self, DeltaStats};
+use crate::transfer::links::{self, HardLinkTracker, InodeKey, LinkPolicy};
+use crate::transfer::plan::TransferPlan;
+use crate::transfer::attributes::FileAttributes;
+use crate::transfer::conflict::{free_name, ConflictResolver, FileFacts, Resolution};
 use crate::transfer::progress::ProgressTracker;
-use crate::utils::error::TransferError;
+use crate::transfer::resume::{ChunkCursor, ResumeInfo, ResumeJournal};
+use crate::transfer::summary::TransferSummary;
+use crate::transfer::sync;
+use crate::transfer::verify::{remote_hash, verify_after_transfer};
+use crate::utils::error::{classify, TransferError};
+use crate::utils::filter::join_relative;
+use crate::utils::file::{calculate_hash, commit_part, mtime_secs, part_path, write_all_at, HashAlgorithm};
+use crate::utils::space;
+use crate::utils::rate::{RateLimiter, Throttle};
+use crate::utils::sparse::{write_sparse_at, SparseWriter};
 use anyhow::{Result};
 use crossbeam_channel::{bounded, Receiver, Sender};
 use ssh2::Sftp;
+use std::collections::HashMap;
 use std::fs::{File, OpenOptions};
-use std::io::{Read, Seek, SeekFrom, Write};
+use std::io::{Read, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::thread;
 use std::future::Future;
 use std::pin::Pin;
@@ -18,6 +34,12 @@ pub struct Downloader {
     session: SshSession,
     config: Arc<Config>,
     remote_home: String,
+    summary: Arc<TransferSummary>,
+    conflicts: ConflictResolver,
+    plan: TransferPlan,
+    hard_links: HardLinkTracker<PathBuf>,
+    remote_inodes: Mutex<HashMap<String, InodeKey>>,
+    limiter: Arc<RateLimiter>,
 }
 
 impl Downloader {
@@ -27,12 +49,33 @@ impl Downloader {
         // 首先获取远程系统的家目录
         let remote_home = Self::detect_remote_home_dir(&session, &config.username)?;
         
+        let conflicts = ConflictResolver::new(config.on_conflict);
+        let plan = TransferPlan::new(config.dry_run);
+        let hard_links = HardLinkTracker::new(config.hard_links);
+        let limiter = Arc::new(RateLimiter::new(
+            config.limit_rate, config.limit_rate_per_file, config.bandwidth_schedule.clone(),
+        ));
+        
         Ok(Self {
             session,
             config: Arc::new(config),
             remote_home,
+            summary: Arc::new(TransferSummary::new()),
+            conflicts,
+            plan,
+            hard_links,
+            remote_inodes: Mutex::new(HashMap::new()),
+            limiter,
         })
     }
+
+    pub fn summary(&self) -> &TransferSummary {
+        &self.summary
+    }
+
+    pub fn plan(&self) -> &TransferPlan {
+        &self.plan
+    }
     
     // 检测远程系统的家目录
     fn detect_remote_home_dir(session: &SshSession, username: &str) -> Result<String> {
@@ -82,7 +125,7 @@ impl Downloader {
         // 检查远程文件是否存在
         let remote_stat = match sftp.stat(Path::new(&remote_path)) {
             Ok(stat) => stat,
-            Err(e) => return Err(anyhow::anyhow!("Remote file does not exist: {}: {}", remote_path, e))
+            Err(e) => return Err(classify(anyhow::Error::from(e).context(format!("Remote file does not exist: {}", remote_path)), local_path, &remote_path)),
         };
 
         if remote_stat.is_dir() {
@@ -91,16 +134,179 @@ impl Downloader {
                     .file_name()
                     .ok_or_else(|| anyhow::anyhow!("Cannot determine directory name from remote path"))?;
                 let new_local_path = local_path.join(file_name);
-                self.download_directory(&sftp, &remote_path, &new_local_path).await
+                let ancestors = self.link_ancestors(&sftp, &remote_path);
+                self.load_remote_inodes(&remote_path);
+                self.download_directory(&sftp, &remote_path, &new_local_path, "", &ancestors).await?;
+                self.create_hard_links();
+                Ok(())
             } else {
                 Err(TransferError::DirectoryNotAllowed.into())
             }
         } else {
             let file_size = remote_stat.size.unwrap_or(0);
+            let mtime = remote_stat.mtime.unwrap_or(0);
             // 确定目标文件路径
             let target_path = self.get_target_file_path(local_path, &remote_path)?;
-            self.download_file(&sftp, &remote_path, &target_path, file_size).await
+
+            // 续传未完成的临时文件时不视为冲突
+            let resuming = self.config.resume && part_path(&target_path).exists();
+            if self.plan.is_enabled() && !resuming && self.conflicts.is_interactive() && target_path.exists() {
+                self.plan.prompt(&remote_path, target_path.display(), file_size);
+                return Ok(());
+            }
+            let target_path = if resuming {
+                target_path
+            } else {
+                match self.resolve_conflict(&sftp, &remote_path, &target_path, file_size, mtime)? {
+                    Some(path) => path,
+                    None => {
+                        println!("Skipping existing file: {}", target_path.display());
+                        self.plan.skip(target_path.display(), "target exists");
+                        self.summary.record_skipped();
+                        return Ok(());
+                    }
+                }
+            };
+
+            // 试运行只记录计划
+            let offset = if resuming { self.resume_offset(&remote_path, &target_path, file_size, mtime) } else { 0 };
+            if self.plan.is_enabled() {
+                self.plan.transfer(&remote_path, target_path.display(), offset, file_size);
+                return Ok(());
+            }
+
+            // 开始传输前确认本地空间足够，避免传输到一半磁盘写满
+            space::ensure_space(&target_path.display().to_string(), file_size - offset, space::local_available(&target_path))?;
+
+            // 本地已有旧版本时只下载变化的块
+            if self.config.delta && !resuming && target_path.is_file() {
+                println!("Delta downloading: {} -> {}", remote_path, target_path.display());
+                let progress = ProgressTracker::new(file_size, &format!("Downloading {}", Path::new(&remote_path).file_name().unwrap_or_default().to_string_lossy()));
+                match Self::try_delta_download(&self.session.client, &remote_path, &target_path, self.limiter.for_file(), Some(&progress)) {
+                    Some(stats) => {
+                        progress.finish();
+                        println!("✅ Delta download completed: {} ({} bytes transferred, {} bytes reused, {:.1}% saved)",
+                                 target_path.display(), stats.literal_bytes, stats.matched_bytes, stats.saved_percent());
+                        return self.finish_file(&sftp, &target_path, &remote_path, file_size);
+                    }
+                    None => progress.finish_with_error("delta transfer not possible"),
+                }
+            }
+
+            // 临时错误按 --retries 重试，连接断开时重新连接，全部成功后再校验和提交
+            // 失败后再次下载时总是从断点记录确认的位置续传
+            let what = format!("Download of {}", remote_path);
+            let mut sftp = Some(sftp);
+            self.session.client.run_reconnecting(&self.config.retry, &what, &mut sftp, |sftp, failed| {
+                let resume = self.config.resume || failed;
+                if self.config.threads > 1 && file_size > self.config.chunk_size as u64 {
+                    // 大文件按分块并行下载
+                    self.download_file_chunked(&remote_path, &target_path, file_size, mtime, resume)
+                } else {
+                    self.download_file(sftp, &remote_path, &target_path, file_size, mtime, resume)
+                }
+            })
+            .map_err(|e| classify(e, &target_path, &remote_path))?;
+            let sftp = match sftp {
+                Some(sftp) => sftp,
+                None => self.session.client.sftp()?,
+            };
+            self.finish_file(&sftp, &target_path, &remote_path, file_size)
+        }
+    }
+
+    // 把远程目录同步到本地目录，只下载有差异的文件
+    pub async fn sync<P: AsRef<Path>>(&self, remote_path_str: &str, local_path: P) -> Result<()> {
+        let sftp = self.session.client.sftp()?;
+        let remote_path = self.resolve_remote_path_str(remote_path_str);
+        println!("Resolved remote path: {}", remote_path);
+
+        match sftp.stat(Path::new(&remote_path)) {
+            Ok(stat) if stat.is_dir() => {}
+            Ok(_) => return Err(anyhow::anyhow!("sync requires a remote directory: {}", remote_path)),
+            Err(e) => return Err(classify(anyhow::Error::from(e).context(format!("Remote directory does not exist: {}", remote_path)), local_path.as_ref(), &remote_path)),
+        }
+        let ancestors = self.link_ancestors(&sftp, &remote_path);
+        self.load_remote_inodes(&remote_path);
+        self.download_directory(&sftp, &remote_path, local_path.as_ref(), "", &ancestors).await?;
+        self.create_hard_links();
+        Ok(())
+    }
+
+    // 启用硬链接检测时一次读取远程目录树中多链接文件的 inode，远程不支持时按普通文件下载
+    fn load_remote_inodes(&self, remote_dir: &str) {
+        if !self.hard_links.is_enabled() {
+            return;
         }
+        match links::remote_inodes(&self.session.client, remote_dir) {
+            Ok(inodes) => *self.remote_inodes.lock().unwrap() = inodes,
+            Err(e) => println!("Warning: could not detect hard links on the remote host ({}); every name will be downloaded in full", e),
+        }
+    }
+
+    // 所有文件下载完成后再创建硬链接，保证链接目标已经写入
+    fn create_hard_links(&self) {
+        for (target, link) in self.hard_links.take_pending() {
+            if self.plan.is_enabled() {
+                self.plan.hard_link(link.display(), target.display());
+                continue;
+            }
+            match links::create_local_hard_link(&target, &link) {
+                Ok(()) => {
+                    log::debug!("Hard linked {} => {}", link.display(), target.display());
+                    self.summary.record_hard_link();
+                }
+                Err(e) => self.summary.record_failure(&link.display().to_string(), &e),
+            }
+        }
+    }
+
+    // 跟随符号链接时记录根目录的真实路径，用于检测链接循环；其他模式不会进入链接目录
+    fn link_ancestors(&self, sftp: &Sftp, remote_dir: &str) -> Vec<String> {
+        if self.config.links != LinkPolicy::Follow {
+            return Vec::new();
+        }
+        let real = sftp.realpath(Path::new(remote_dir))
+            .map(|path| path.to_string_lossy().to_string())
+            .unwrap_or_else(|_| remote_dir.to_string());
+        vec![real]
+    }
+
+    // 读取远程目录；跟随符号链接时用链接目标的属性替换链接本身的属性
+    // 返回的标记表示条目是否为符号链接，悬空链接保留原有属性
+    fn read_remote_dir(&self, sftp: &Sftp, remote_dir: &str) -> Result<Vec<(PathBuf, ssh2::FileStat, bool)>> {
+        let follow = self.config.links == LinkPolicy::Follow;
+        Ok(sftp.readdir(Path::new(remote_dir))?
+            .into_iter()
+            .map(|(path, stat)| {
+                if !stat.file_type().is_symlink() {
+                    return (path, stat, false);
+                }
+                match sftp.stat(&path) {
+                    Ok(target) if follow => (path, target, true),
+                    _ => (path, stat, true),
+                }
+            })
+            .collect())
+    }
+
+    // 在本地重新创建远程符号链接，链接目标保持原样不做转换
+    fn copy_symlink(&self, sftp: &Sftp, remote_path: &str, local_path: &Path) -> Result<()> {
+        let target = sftp.readlink(Path::new(remote_path))?;
+        if std::fs::read_link(local_path).is_ok_and(|existing| existing == target) {
+            log::debug!("Up to date: {}", local_path.display());
+            self.plan.skip(local_path.display(), "up to date");
+            self.summary.record_skipped();
+            return Ok(());
+        }
+        if self.plan.is_enabled() {
+            self.plan.symlink(local_path.display(), target.display());
+            return Ok(());
+        }
+        links::create_local_symlink(&target, local_path)?;
+        println!("Created symlink: {} -> {}", local_path.display(), target.display());
+        self.summary.record_symlink();
+        Ok(())
     }
 
     // 解析远程路径字符串，处理波浪线等特殊字符
@@ -137,6 +343,9 @@ impl Downloader {
             // 检查父目录是否存在
             if parent.exists() || parent.as_os_str().is_empty() {
                 Ok(local_dir.to_path_buf())
+            } else if self.plan.is_enabled() {
+                self.plan.create_dir(parent.display());
+                Ok(local_dir.to_path_buf())
             } else {
                 // 尝试创建父目录
                 std::fs::create_dir_all(parent)?;
@@ -148,42 +357,53 @@ impl Downloader {
         }
     }
 
+    // 本地目标文件已存在时按冲突策略决定写入路径，返回 None 表示跳过
+    fn resolve_conflict(&self, sftp: &Sftp, remote_path: &str, local_path: &Path, size: u64, mtime: u64) -> Result<Option<PathBuf>> {
+        let metadata = match std::fs::metadata(local_path) {
+            Ok(metadata) => metadata,
+            Err(_) => return Ok(Some(local_path.to_path_buf())),
+        };
+
+        let source = FileFacts { size, mtime };
+        let existing = FileFacts { size: metadata.len(), mtime: mtime_secs(&metadata) };
+        let algorithm = self.config.hash_algorithm;
+        let resolution = self.conflicts.resolve(&local_path.display().to_string(), source, existing, || {
+            let local = calculate_hash(local_path, algorithm)?;
+            let remote = remote_hash(&self.session.client, sftp, remote_path, algorithm)?;
+            Ok(local != remote)
+        })?;
+
+        Ok(match resolution {
+            Resolution::Overwrite => Some(local_path.to_path_buf()),
+            Resolution::Skip => None,
+            Resolution::Rename => {
+                let renamed = PathBuf::from(free_name(local_path, |candidate| Path::new(candidate).exists()));
+                println!("Saving as {} to avoid overwriting {}", renamed.display(), local_path.display());
+                Some(renamed)
+            }
+        })
+    }
+
     // 实现带断点续传的文件下载
-    async fn download_file(&self, sftp: &Sftp, remote_path: &str, local_path: &Path, file_size: u64) -> Result<()> {
+    fn download_file(&self, sftp: &Sftp, remote_path: &str, local_path: &Path, file_size: u64, mtime: u64, resume: bool) -> Result<()> {
         println!("Downloading file: {} -> {} ({} bytes)", remote_path, local_path.display(), file_size);
 
-        // 断点续传逻辑：检查本地文件是否存在
-        let mut offset = 0;
         let progress = ProgressTracker::new(file_size, &format!("Downloading {}", Path::new(remote_path).file_name().unwrap_or_default().to_string_lossy()));
-        
-        if self.config.resume && local_path.exists() {
-            // 获取本地本文件大小
-            let metadata = std::fs::metadata(local_path)?;
-            let local_size = metadata.len();
-            
-            // 确保本地文件不大于远程文件
-            if local_size <= file_size {
-                offset = local_size;
-                println!("Resuming download from offset: {} bytes", offset);
-                progress.update(offset); // 更新进度条以显示已下载部分
-            } else {
-                println!("Local file is larger than remote file. Starting download from beginning.");
-                // 本地文件异常，删除并重新开始
-                std::fs::remove_file(local_path)?;
-            }
+
+        // 断点续传逻辑：根据断点记录确定续传位置
+        let (journal, offset) = Self::open_sequential_journal(&self.config, remote_path, local_path, file_size, mtime, resume)?;
+        if offset > 0 {
+            println!("Resuming download from offset: {} bytes", offset);
+            progress.update(offset); // 更新进度条以显示已下载部分
         }
 
-        // 创建或打开本地文件
-        let mut local_file = if offset > 0 {
-            // 以追加模式打开文件
-            OpenOptions::new()
-                .write(true)
-                .append(true)
-                .open(local_path)?
-        } else {
-            // 创建新文件
-            File::create(local_path)?
-        };
+        // 创建或打开临时文件，下载完成后再重命名为目标文件
+        // 只有新建的临时文件可以跳过全零块，续传部分从 offset 处截断后追加
+        let local_file = Self::open_local_at(&part_path(local_path), offset)?;
+        if self.config.preallocate && !self.config.sparse {
+            space::preallocate(&local_file, file_size);
+        }
+        let mut local_file = SparseWriter::new(local_file, self.config.sparse && offset == 0)?;
 
         // 打开远程文件并设置偏移量
         let mut remote_file = sftp.open(Path::new(remote_path))?;
@@ -193,183 +413,739 @@ impl Downloader {
 
         let mut buffer = vec![0u8; self.config.chunk_size];
         let mut total_transferred = offset;
+        let mut cursor = journal.as_ref().map(|j| ChunkCursor::new(j, self.config.chunk_size as u64, offset));
+        let throttle = self.limiter.for_file();
 
         loop {
             match remote_file.read(&mut buffer) {
                 Ok(0) => break, // EOF
                 Ok(bytes_read) => {
+                    throttle.consume(bytes_read);
                     local_file.write_all(&buffer[..bytes_read])?;
+                    if let Some(cursor) = cursor.as_mut() {
+                        cursor.advance(&buffer[..bytes_read])?;
+                    }
                     total_transferred += bytes_read as u64;
                     progress.update(total_transferred);
                 }
                 Err(e) => {
                     progress.finish_with_error(&e.to_string());
+                    if let Some(journal) = &journal {
+                        journal.flush()?;
+                    }
                     return Err(e.into());
                 }
             }
         }
 
         // 确保数据写入磁盘
-        local_file.flush()?;
+        local_file.finish()?;
+        if let Some(journal) = &journal {
+            journal.flush()?;
+            journal.finish()?;
+        }
 
         progress.finish();
         println!("✅ Download completed: {}", local_path.display());
         Ok(())
     }
 
+    // 单个文件下载完成后校验临时文件，通过后重命名为目标文件并记录统计
+    fn finish_file(&self, sftp: &Sftp, local_path: &Path, remote_path: &str, file_size: u64) -> Result<()> {
+        let result = verify_after_transfer(&self.config, &self.session.client, sftp, &part_path(local_path), remote_path, &self.summary)
+            .and_then(|_| commit_part(local_path))
+            .and_then(|_| self.preserve_attributes(sftp, remote_path, local_path));
+        if let Err(e) = result {
+            self.summary.record_failure(remote_path, &e);
+            return Err(e);
+        }
+        self.summary.record_transferred(file_size);
+        Ok(())
+    }
+
+    // 同步模式下按大小、修改时间或摘要判断本地文件是否需要更新
+    fn sync_needs_copy(&self, options: sync::SyncOptions, sftp: &Sftp, remote_path: &str, local_path: &Path, size: u64, mtime: u64) -> Result<bool> {
+        let existing = std::fs::metadata(local_path)
+            .ok()
+            .filter(|metadata| metadata.is_file())
+            .map(|metadata| FileFacts { size: metadata.len(), mtime: mtime_secs(&metadata) });
+        let algorithm = self.config.hash_algorithm;
+        options.needs_copy(FileFacts { size, mtime }, existing, || {
+            let local = calculate_hash(local_path, algorithm)?;
+            let remote = remote_hash(&self.session.client, sftp, remote_path, algorithm)?;
+            Ok(local != remote)
+        })
+    }
+
+    // 同步时删除本地目录中远程已不存在的条目
+    // 被排除的本地条目不会删除
+    fn delete_extraneous(&self, entries: &[(PathBuf, ssh2::FileStat, bool)], local_dir: &Path, relative: &str) -> Result<()> {
+        let source: HashMap<String, bool> = entries
+            .iter()
+            .filter_map(|(path, stat, _)| Some((path.file_name()?.to_string_lossy().to_string(), stat.is_dir())))
+            .collect();
+        let mut local_entries = Vec::new();
+        for entry in std::fs::read_dir(local_dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            let is_dir = entry.file_type()?.is_dir();
+            if self.config.filter.allows(&join_relative(relative, &name), is_dir) {
+                local_entries.push((name, is_dir));
+            }
+        }
+
+        for (name, is_dir) in sync::extraneous(&source, local_entries) {
+            let path = local_dir.join(&name);
+            if self.plan.is_enabled() {
+                self.plan.delete(path.display());
+                continue;
+            }
+            println!("Deleting {}", path.display());
+            match sync::remove_local(&path, is_dir) {
+                Ok(()) => self.summary.record_deleted(),
+                Err(e) => self.summary.record_failure(&path.display().to_string(), &e),
+            }
+        }
+        Ok(())
+    }
+
+    // 增量下载到临时文件，远程无法生成增量时清理临时文件并返回 None，由调用方回退到完整下载
+    // 增量数据与普通下载一样受限速约束，progress 为 None 时由调用方在完成后计入进度
+    fn try_delta_download(client: &SshClient, remote_path: &str, local_path: &Path, throttle: Throttle, progress: Option<&ProgressTracker>) -> Option<DeltaStats> {
+        let part = part_path(local_path);
+        let result = delta::download_delta(client, remote_path, local_path, &part, throttle, |bytes| {
+            if let Some(progress) = progress {
+                progress.add_bytes(bytes);
+            }
+        });
+        match result {
+            Ok(stats) => {
+                log::info!("Delta download of {}: {} bytes transferred, {} bytes reused",
+                           remote_path, stats.literal_bytes, stats.matched_bytes);
+                Some(stats)
+            }
+            Err(e) => {
+                println!("Delta transfer not possible for {}: {}; falling back to a full download", remote_path, e);
+                let _ = std::fs::remove_file(&part);
+                None
+            }
+        }
+    }
+
+    // 按配置把远程文件或目录的属性应用到本地
+    fn preserve_attributes(&self, sftp: &Sftp, remote_path: &str, local_path: &Path) -> Result<()> {
+        let which = self.config.preserved_attributes();
+        if which.is_empty() {
+            return Ok(());
+        }
+        let stat = sftp.stat(Path::new(remote_path))?;
+        FileAttributes::from_remote(&stat).apply_local(local_path, which)
+    }
+
+    // 顺序下载时打开临时文件的断点记录，返回记录和续传偏移量
+    // 不超过一个分块的小文件不记录断点，总是从头下载
+    fn open_sequential_journal(
+        config: &Config,
+        remote_path: &str,
+        local_path: &Path,
+        file_size: u64,
+        mtime: u64,
+        resume: bool,
+    ) -> Result<(Option<ResumeJournal>, u64)> {
+        let chunk_size = config.chunk_size as u64;
+        if file_size <= chunk_size {
+            return Ok((None, 0));
+        }
+
+        let part = part_path(local_path);
+        let (journal, resuming) = ResumeJournal::open(
+            Self::journal_path(config, local_path),
+            remote_path,
+            file_size,
+            mtime,
+            chunk_size,
+            config.hash_algorithm,
+            resume && part.exists(),
+        )?;
+        if resuming {
+            let corrupted = journal.verify_local(&part)?;
+            if corrupted > 0 {
+                println!("{} downloaded chunk(s) of {} failed verification and will be downloaded again",
+                         corrupted, local_path.display());
+            }
+        }
+        let offset = journal.completed_prefix();
+        Ok((Some(journal), offset))
+    }
+
+    // 打开本地文件并定位到续传位置，丢弃续传位置之后未确认的数据
+    fn open_local_at(local_path: &Path, offset: u64) -> Result<File> {
+        crate::utils::file::ensure_parent_dir(local_path)?;
+        if offset == 0 {
+            return Ok(File::create(local_path)?);
+        }
+        let mut local_file = OpenOptions::new()
+            .write(true)
+            .open(local_path)?;
+        local_file.set_len(offset)?;
+        local_file.seek(SeekFrom::Start(offset))?;
+        Ok(local_file)
+    }
+
+    // 只有启用 --resume 时才把下载的断点记录写到临时文件旁边，否则只保存在内存中
+    fn journal_path(config: &Config, local_path: &Path) -> Option<PathBuf> {
+        if !config.resume {
+            return None;
+        }
+        Some(ResumeInfo::resume_file_path(part_path(local_path)))
+    }
+
+    // 根据临时文件的断点记录计算续传偏移量，没有匹配的记录时从头下载
+    fn resume_offset(&self, remote_path: &str, local_path: &Path, size: u64, mtime: u64) -> u64 {
+        let chunk_size = self.config.chunk_size as u64;
+        Self::journal_path(&self.config, local_path)
+            .and_then(|path| ResumeInfo::load_matching(&path, remote_path, size, mtime, chunk_size))
+            .map(|info| info.completed_prefix())
+            .unwrap_or(0)
+    }
+
+    // 计算目录下载时文件的续传偏移量，返回 None 表示文件已完整下载
+    fn planned_offset(&self, remote_path: &str, local_path: &Path, size: u64, mtime: u64) -> Option<u64> {
+        if !self.config.resume {
+            return Some(0);
+        }
+        // 存在未完成的临时文件时，根据断点记录续传
+        if part_path(local_path).exists() {
+            return Some(self.resume_offset(remote_path, local_path, size, mtime));
+        }
+        // 同步模式由大小和修改时间决定是否需要下载
+        if self.config.sync_options().is_some() {
+            return Some(0);
+        }
+        // 没有临时文件且目标文件大小一致，说明上次已完整下载
+        let local_size = std::fs::metadata(local_path).map(|m| m.len()).ok();
+        if local_size == Some(size) {
+            None
+        } else {
+            Some(0)
+        }
+    }
+
+    // 将大文件划分为多个分块，由多个独立的SSH会话并行下载
+    fn download_file_chunked(&self, remote_path: &str, local_path: &Path, file_size: u64, mtime: u64, resume: bool) -> Result<()> {
+        let chunk_size = self.config.chunk_size as u64;
+        println!("Downloading file in chunks: {} -> {} ({} bytes, {} byte chunks)",
+                 remote_path, local_path.display(), file_size, chunk_size);
+
+        // 读取或创建临时文件的断点记录，源文件变化时重新开始
+        let part = part_path(local_path);
+        let (journal, resuming) = ResumeJournal::open(
+            Self::journal_path(&self.config, local_path),
+            remote_path,
+            file_size,
+            mtime,
+            chunk_size,
+            self.config.hash_algorithm,
+            resume && part.exists(),
+        )?;
+        if resuming {
+            let corrupted = journal.verify_local(&part)?;
+            if corrupted > 0 {
+                println!("{} downloaded chunk(s) of {} failed verification and will be downloaded again",
+                         corrupted, local_path.display());
+            }
+        }
+        let journal = Arc::new(journal);
+
+        let progress = Arc::new(ProgressTracker::new(file_size, &format!("Downloading {}", Path::new(remote_path).file_name().unwrap_or_default().to_string_lossy())));
+        if resuming {
+            let offset = journal.transferred_size();
+            println!("Resuming chunked download: {} of {} bytes already downloaded", offset, file_size);
+            progress.update(offset);
+        }
+
+        // 预分配临时文件，各分块使用定位写入
+        crate::utils::file::ensure_parent_dir(&part)?;
+        let local_file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(!resuming)
+            .open(&part)?;
+        local_file.set_len(file_size)?;
+        if self.config.preallocate && !self.config.sparse {
+            space::preallocate(&local_file, file_size);
+        }
+        let local_file = Arc::new(local_file);
+
+        let pending = journal.pending_chunks();
+        let (tx, rx): (Sender<ChunkTask>, Receiver<ChunkTask>) = bounded(100);
+        // 同一文件的所有分块共用单文件限速
+        let throttle = self.limiter.for_file();
+
+        // 每个工作线程使用独立的SSH会话
+        let mut handles = Vec::new();
+        for _ in 0..self.config.threads.min(pending.len()) {
+            let rx = rx.clone();
+            let session = self.session.clone_session()?;
+            let remote_path = remote_path.to_string();
+            let local_file = Arc::clone(&local_file);
+            let journal = Arc::clone(&journal);
+            let progress = Arc::clone(&progress);
+            let throttle = throttle.clone();
+            let retry = self.config.retry;
+            // 续传时未完成的分块可能残留旧数据，只有全新下载才跳过全零块
+            let sparse = self.config.sparse && !resuming;
+
+            let handle = thread::spawn(move || {
+                // SFTP 通道和远程文件在第一个分块时打开，打开失败也按重试和重连处理
+                let mut sftp = None;
+                let mut remote_file = None;
+                while let Ok(task) = rx.recv() {
+                    let what = format!("Download of chunk {} of {}", task.chunk_id, remote_path);
+                    // 失败后重新打开远程文件，重新连接后旧的文件句柄已经失效
+                    let result = session.run_reconnecting(&retry, &what, &mut sftp, |sftp, failed| {
+                        if failed {
+                            remote_file = None;
+                        }
+                        let remote_file = match &mut remote_file {
+                            Some(file) => file,
+                            None => remote_file.insert(sftp.open(Path::new(&remote_path))?),
+                        };
+                        Self::download_chunk_worker(remote_file, &local_file, &task, &progress, &throttle, journal.hash_algorithm(), sparse)
+                    })
+                    .and_then(|checksum| journal.complete_chunk(task.chunk_id, Some(checksum)));
+                    if let Err(e) = result {
+                        eprintln!("Download error for chunk {} of {}: {}", task.chunk_id, remote_path, e);
+                    }
+                }
+            });
+            handles.push(handle);
+        }
+        drop(rx);
+
+        for (chunk_id, start, end) in pending {
+            // 所有工作线程都已退出时停止分发
+            if tx.send(ChunkTask { chunk_id, start, end }).is_err() {
+                break;
+            }
+        }
+        drop(tx);
+
+        for handle in handles {
+            handle.join().map_err(|_| TransferError::ThreadJoinError)?;
+        }
+
+        local_file.sync_all()?;
+        journal.flush()?;
+
+        let failed = journal.pending_chunks().len();
+        if failed > 0 {
+            progress.finish_with_error("some chunks failed");
+            return Err(anyhow::anyhow!(
+                "{} chunk(s) of {} failed to download; rerun with --resume to fetch the missing chunks",
+                failed, remote_path
+            ));
+        }
+
+        journal.finish()?;
+        progress.finish();
+        println!("✅ Download completed: {}", local_path.display());
+        Ok(())
+    }
+
+    fn download_chunk_worker(
+        remote_file: &mut ssh2::File,
+        local_file: &File,
+        task: &ChunkTask,
+        progress: &ProgressTracker,
+        throttle: &Throttle,
+        hash_algorithm: HashAlgorithm,
+        sparse: bool,
+    ) -> Result<String> {
+        remote_file.seek(SeekFrom::Start(task.start))?;
+        let mut hasher = hash_algorithm.hasher();
+        // 失败时撤回本次计入的进度，重试的分块不会重复计数
+        let mut counted = progress.attempt();
+
+        let mut buffer = vec![0u8; (task.end - task.start + 1).min(8 * 1024 * 1024) as usize];
+        let mut offset = task.start;
+        while offset <= task.end {
+            let want = ((task.end - offset + 1) as usize).min(buffer.len());
+            let bytes_read = remote_file.read(&mut buffer[..want])?;
+            if bytes_read == 0 {
+                return Err(anyhow::anyhow!("Unexpected end of remote file at offset {}", offset));
+            }
+            throttle.consume(bytes_read);
+            if sparse {
+                write_sparse_at(local_file, &buffer[..bytes_read], offset)?;
+            } else {
+                write_all_at(local_file, &buffer[..bytes_read], offset)?;
+            }
+            hasher.update(&buffer[..bytes_read]);
+            offset += bytes_read as u64;
+            counted.add_bytes(bytes_read as u64);
+        }
+        counted.commit();
+        // 返回分块校验和，写入断点记录
+        Ok(hasher.finalize())
+    }
+
     // 使用Pin<Box<dyn Future>> 返回类型来处理异步递归
     fn download_directory<'a>(
         &'a self,
         sftp: &'a Sftp,
         remote_dir: &'a str,
         local_dir: &'a Path,
+        relative: &'a str,
+        ancestors: &'a [String],
     ) -> Pin<Box<dyn Future<Output = Result<()>> + 'a>> {
         Box::pin(async move {
-            // 确保本地目录存在
+            // 确保本地目录存在，试运行时只记录
             if !local_dir.exists() {
-                std::fs::create_dir_all(local_dir)?;
+                if self.plan.is_enabled() {
+                    self.plan.create_dir(local_dir.display());
+                } else {
+                    std::fs::create_dir_all(local_dir)?;
+                }
             } else if !local_dir.is_dir() {
                 return Err(anyhow::anyhow!("Local path exists but is not a directory: {}", local_dir.display()));
             }
 
             // 获取远程目录内容
-            let entries = sftp.readdir(Path::new(remote_dir))?;
+            let entries = self.read_remote_dir(sftp, remote_dir)?;
+
+            // 同步时先删除远程已不存在的本地条目，类型变化的条目随后重新下载
+            let sync_options = self.config.sync_options();
+            if sync_options.is_some_and(|options| options.delete) && local_dir.is_dir() {
+                self.delete_extraneous(&entries, local_dir, relative)?;
+            }
             
             // 计算总下载大小和收集文件信息
             let mut total_size = 0u64;
             let mut files_to_download = Vec::new();
             let mut dirs_to_download = Vec::new();
             
-            for (path, stat) in entries {
+            for (path, stat, is_link) in entries {
                 let path_str = path.to_string_lossy().to_string();
                 let file_name = path.file_name()
                     .ok_or_else(|| anyhow::anyhow!("Cannot determine file name"))?
                     .to_string_lossy().to_string();
                 let local_path = local_dir.join(&file_name);
+
+                // 被排除的目录整棵跳过，不再进入
+                let entry_relative = join_relative(relative, &file_name);
+                if !self.config.filter.allows(&entry_relative, stat.is_dir()) {
+                    log::debug!("Excluded: {}", path_str);
+                    self.plan.skip(&path_str, "excluded");
+                    self.summary.record_excluded();
+                    continue;
+                }
+
+                // 跟随模式下仍是链接属性的条目是悬空链接
+                if stat.file_type().is_symlink() {
+                    match self.config.links {
+                        LinkPolicy::Copy => {
+                            if let Err(e) = self.copy_symlink(sftp, &path_str, &local_path) {
+                                self.summary.record_failure(&path_str, &e);
+                            }
+                        }
+                        LinkPolicy::Skip => {
+                            log::debug!("Skipping symlink: {}", path_str);
+                            self.plan.skip(&path_str, "symlink");
+                            self.summary.record_skipped();
+                        }
+                        LinkPolicy::Follow => {
+                            println!("Warning: skipping dangling symlink: {}", path_str);
+                            self.plan.skip(&path_str, "dangling symlink");
+                            self.summary.record_skipped();
+                        }
+                    }
+                    continue;
+                }
                 
                 if stat.is_dir() {
-                    // 收集子目录信息，稍后处理
+                    // 跟随链接进入已经在当前路径上的目录会无限递归
                     let remote_subdir = format!("{}/{}", remote_dir, file_name);
-                    dirs_to_download.push((remote_subdir, local_path));
+                    let mut subdir_ancestors = ancestors.to_vec();
+                    if let Some(parent_real) = ancestors.last() {
+                        let real = if is_link {
+                            sftp.realpath(&path)?.to_string_lossy().to_string()
+                        } else {
+                            format!("{}/{}", parent_real.trim_end_matches('/'), file_name)
+                        };
+                        if ancestors.contains(&real) {
+                            println!("Warning: skipping symlink loop: {} -> {}", path_str, real);
+                            self.plan.skip(&path_str, "symlink loop");
+                            self.summary.record_skipped();
+                            continue;
+                        }
+                        subdir_ancestors.push(real);
+                    }
+                    // 收集子目录信息，稍后处理
+                    dirs_to_download.push((remote_subdir, local_path, entry_relative, subdir_ancestors));
                 } else {
                     // 添加文件到下载列表
                     let size = stat.size.unwrap_or(0);
+                    let mtime = stat.mtime.unwrap_or(0);
+                    let inode = self.remote_inodes.lock().unwrap().get(&path_str).copied();
                     
-                    // 如果启用断点续传，检查本地文件
-                    let mut effective_size = size;
-                    if self.config.resume && local_path.exists() {
-                        if let Ok(metadata) = std::fs::metadata(&local_path) {
-                            let local_size = metadata.len();
-                            if local_size < size {
-                                // 只下载剩余部分
-                                effective_size = size - local_size;
-                            } else if local_size == size {
-                                // 文件已完成，跳过
-                                println!("Skipping already downloaded file: {}", local_path.display());
+                    // 如果启用断点续传，根据断点记录检查本地文件
+                    let offset = match self.planned_offset(&path_str, &local_path, size, mtime) {
+                        Some(offset) => offset,
+                        None => {
+                            // 文件已完成，跳过
+                            println!("Skipping already downloaded file: {}", local_path.display());
+                            self.plan.skip(local_path.display(), "already downloaded");
+                            self.summary.record_skipped();
+                            continue;
+                        }
+                    };
+
+                    // 从头下载时按冲突策略处理已存在的本地文件，同步模式只下载有差异的文件
+                    let local_path = if offset > 0 {
+                        local_path
+                    } else if let Some(options) = sync_options {
+                        if !self.sync_needs_copy(options, sftp, &path_str, &local_path, size, mtime)? {
+                            self.hard_links.remember(inode, &local_path);
+                            log::debug!("Up to date: {}", local_path.display());
+                            self.plan.skip(local_path.display(), "up to date");
+                            self.summary.record_skipped();
+                            continue;
+                        }
+                        local_path
+                    } else if self.plan.is_enabled() && self.conflicts.is_interactive() && local_path.exists() {
+                        self.plan.prompt(&path_str, local_path.display(), size);
+                        continue;
+                    } else {
+                        match self.resolve_conflict(sftp, &path_str, &local_path, size, mtime)? {
+                            Some(path) => path,
+                            None => {
+                                println!("Skipping existing file: {}", local_path.display());
+                                self.plan.skip(local_path.display(), "target exists");
+                                self.summary.record_skipped();
                                 continue;
                             }
                         }
-                    }
+                    };
                     
-                    total_size += effective_size;
-                    files_to_download.push((path_str, local_path, size));
-                }
-            }
-
-            // 创建单个总进度条跟踪当前目录的所有文件
-            let progress = Arc::new(ProgressTracker::new(
-                total_size, 
-                &format!("Downloading from {}", remote_dir)
-            ));
-
-            // 创建下载任务
-            let (tx, rx): (Sender<DownloadTask>, Receiver<DownloadTask>) = bounded(100);
-
-            // 启动工作线程
-            let mut handles = Vec::new();
-            for _ in 0..self.config.threads {
-                let rx = rx.clone();
-                let session = self.session.clone_session()?;
-                let config = Arc::clone(&self.config);
-                let progress = Arc::clone(&progress);
-
-                let handle = thread::spawn(move || {
-                    let sftp = session.sftp().unwrap();
-                    while let Ok(task) = rx.recv() {
-                        if let Err(e) = Self::download_file_worker(&sftp, &task, &config) {
-                            eprintln!("Download error for {}: {}", task.remote_path, e);
-                        } else {
-                            progress.add_bytes(task.effective_size);
-                        }
+                    // 同一 inode 的内容只下载一次，其他名字稍后创建为硬链接
+                    if self.hard_links.register(inode, &local_path) {
+                        log::debug!("Hard link: {}", path_str);
+                        continue;
                     }
-                });
-                handles.push(handle);
-            }
 
-            // 发送下载任务
-            for (remote_path, local_path, size) in files_to_download {
-                let mut offset = 0;
-                let mut effective_size = size;
-                
-                // 如果启用断点续传，计算偏移量
-                if self.config.resume && local_path.exists() {
-                    if let Ok(metadata) = std::fs::metadata(&local_path) {
-                        let local_size = metadata.len();
-                        if local_size < size {
-                            offset = local_size;
-                            effective_size = size - local_size;
-                        }
-                    }
+                    total_size += size - offset;
+                    self.plan.transfer(&path_str, local_path.display(), offset, size);
+                    files_to_download.push(DownloadTask {
+                        remote_path: path_str,
+                        local_path,
+                        size,
+                        mtime,
+                        effective_size: size - offset,
+                        attributes: FileAttributes::from_remote(&stat),
+                    });
                 }
-                
-                let task = DownloadTask {
-                    remote_path,
-                    local_path,
-                    offset,
-                    effective_size,
-                };
-                tx.send(task)?;
             }
-            drop(tx);
 
-            // 等待所有工作线程完成
-            for handle in handles {
-                handle.join().map_err(|_| TransferError::ThreadJoinError)?;
+            // 试运行只记录计划，不启动传输
+            if !self.plan.is_enabled() {
+                // 开始传输前确认本地空间足够，避免传输到一半磁盘写满
+                if total_size > 0 {
+                    space::ensure_space(&local_dir.display().to_string(), total_size, space::local_available(local_dir))?;
+                }
+                if self.config.archive_mode {
+                    self.run_download_archive(sftp, remote_dir, files_to_download, total_size);
+                } else {
+                    self.run_download_tasks(remote_dir, files_to_download, total_size)?;
+                }
             }
 
-            progress.finish();
-            
             // 处理完当前目录中的文件后，顺序处理子目录
             // 这样避免同时创建太多进度条
-            for (remote_subdir, local_subpath) in dirs_to_download {
-                self.download_directory(sftp, &remote_subdir, &local_subpath).await?;
+            for (remote_subdir, local_subpath, subdir_relative, subdir_ancestors) in dirs_to_download {
+                self.download_directory(sftp, &remote_subdir, &local_subpath, &subdir_relative, &subdir_ancestors).await?;
+            }
+
+            // 目录内容全部写入后再应用目录属性，否则写入文件会改变目录的修改时间
+            if !self.plan.is_enabled() {
+                self.preserve_attributes(sftp, remote_dir, local_dir)?;
             }
 
             Ok(())
         })
     }
 
-    fn download_file_worker(sftp: &Sftp, task: &DownloadTask, config: &Config) -> Result<()> {
-        // 准备本地文件
-        let mut local_file = if task.offset > 0 {
-            OpenOptions::new()
-                .write(true)
-                .append(true)
-                .open(&task.local_path)?
-        } else {
-            // 确保父目录存在
-            if let Some(parent) = task.local_path.parent() {
-                if !parent.exists() {
-                    std::fs::create_dir_all(parent)?;
+    // 归档模式：当前目录需要下载的文件通过一个 tar 流接收，逐个写入临时文件后校验并提交
+    fn run_download_archive(&self, sftp: &Sftp, remote_dir: &str, tasks: Vec<DownloadTask>, total_size: u64) {
+        if tasks.is_empty() {
+            return;
+        }
+        let progress = ProgressTracker::new(total_size, &format!("Downloading from {}", remote_dir));
+        let attributes = self.config.preserved_attributes();
+        let names: Vec<String> = tasks.iter()
+            .map(|task| Path::new(&task.remote_path).file_name().unwrap_or_default().to_string_lossy().to_string())
+            .collect();
+        let mut pending: HashMap<String, DownloadTask> = names.iter().cloned().zip(tasks).collect();
+        // 已提交的文件，供归档中的硬链接条目链接或复制
+        let mut received: HashMap<String, PathBuf> = HashMap::new();
+
+        let result = archive::receive_archive(&self.session.client, remote_dir, &names, self.config.stream_compression,
+                                               progress.wire_bytes(), self.limiter.shared(), |name, entry| {
+            let Some(task) = pending.remove(name) else {
+                log::debug!("Unexpected entry in archive: {}", name);
+                return;
+            };
+            progress.set_current_file(&task.remote_path);
+            let part = part_path(&task.local_path);
+            let verify_and_commit = || {
+                verify_after_transfer(&self.config, &self.session.client, sftp, &part, &task.remote_path, &self.summary)
+                    .and_then(|_| commit_part(&task.local_path))
+            };
+            let written = match entry {
+                ArchiveEntry::File(reader) => self.write_archive_entry(reader, &part, task.size)
+                    .and_then(|_| verify_and_commit()),
+                ArchiveEntry::HardLink(target) => match received.get(&target) {
+                    // 启用 --hard-links 时直接链接到已提交的目标文件，否则复制一份
+                    Some(source) if self.config.hard_links => links::create_local_hard_link(source, &task.local_path),
+                    Some(source) => std::fs::copy(source, &part).map(|_| ()).map_err(Into::into)
+                        .and_then(|_| verify_and_commit()),
+                    None => Err(anyhow::anyhow!("Hard link target {} is not part of the archive", target)),
+                },
+            };
+            let result = written
+                .and_then(|_| task.attributes.apply_local(&task.local_path, attributes));
+            match result {
+                Ok(()) => {
+                    progress.add_bytes(task.effective_size);
+                    self.summary.record_transferred(task.size);
+                    received.insert(name.to_string(), task.local_path);
+                }
+                Err(e) => {
+                    let e = classify(e, &task.local_path, &task.remote_path);
+                    eprintln!("Download error for {}: {}", task.remote_path, e);
+                    let _ = std::fs::remove_file(&part);
+                    self.summary.record_failure(&task.remote_path, &e);
                 }
             }
-            File::create(&task.local_path)?
-        };
+        });
+        if let Err(e) = result {
+            eprintln!("Archive download from {} failed: {}", remote_dir, e);
+        }
+
+        // 远程读取失败的文件不会出现在归档中
+        for task in pending.into_values() {
+            self.summary.record_failure(&task.remote_path, &anyhow::anyhow!("File was not included in the archive"));
+        }
+        progress.finish();
+    }
+
+    // 把归档条目写入本地临时文件，长度与远程文件不一致时报错
+    fn write_archive_entry(&self, reader: &mut dyn Read, part: &Path, size: u64) -> Result<()> {
+        crate::utils::file::ensure_parent_dir(part)?;
+        let local_file = File::create(part)?;
+        if self.config.preallocate && !self.config.sparse {
+            space::preallocate(&local_file, size);
+        }
+        let mut local_file = SparseWriter::new(local_file, self.config.sparse)?;
+        let mut buffer = vec![0u8; self.config.chunk_size];
+        let mut written = 0u64;
+        loop {
+            let bytes_read = reader.read(&mut buffer)?;
+            if bytes_read == 0 {
+                break;
+            }
+            local_file.write_all(&buffer[..bytes_read])?;
+            written += bytes_read as u64;
+        }
+        local_file.finish()?;
+        if written != size {
+            return Err(anyhow::anyhow!("Archive entry has {} bytes, expected {}", written, size));
+        }
+        Ok(())
+    }
+
+    // 用多个工作线程下载同一目录中的文件
+    fn run_download_tasks(&self, remote_dir: &str, tasks: Vec<DownloadTask>, total_size: u64) -> Result<()> {
+        // 创建单个总进度条跟踪当前目录的所有文件
+        let progress = Arc::new(ProgressTracker::new(
+            total_size, 
+            &format!("Downloading from {}", remote_dir)
+        ));
+
+        // 创建下载任务
+        let (tx, rx): (Sender<DownloadTask>, Receiver<DownloadTask>) = bounded(100);
+
+        // 启动工作线程
+        let mut handles = Vec::new();
+        for _ in 0..self.config.threads {
+            let rx = rx.clone();
+            let session = self.session.clone_session()?;
+            let config = Arc::clone(&self.config);
+            let progress = Arc::clone(&progress);
+            let summary = Arc::clone(&self.summary);
+            let limiter = Arc::clone(&self.limiter);
+            let attributes = config.preserved_attributes();
+
+            let handle = thread::spawn(move || {
+                let mut sftp = None;
+                while let Ok(task) = rx.recv() {
+                    let what = format!("Download of {}", task.remote_path);
+                    let result = session.run_reconnecting(&config.retry, &what, &mut sftp, |sftp, failed| {
+                        Self::download_file_worker(&session, sftp, &task, &config, &limiter, config.resume || failed)
+                            .and_then(|_| verify_after_transfer(&config, &session, sftp, &part_path(&task.local_path), &task.remote_path, &summary))
+                            .and_then(|_| commit_part(&task.local_path))
+                            .and_then(|_| task.attributes.apply_local(&task.local_path, attributes))
+                    });
+                    if let Err(e) = result {
+                        let e = classify(e, &task.local_path, &task.remote_path);
+                        eprintln!("Download error for {}: {}", task.remote_path, e);
+                        summary.record_failure(&task.remote_path, &e);
+                    } else {
+                        progress.add_bytes(task.effective_size);
+                        summary.record_transferred(task.size);
+                    }
+                }
+            });
+            handles.push(handle);
+        }
+
+        // 发送下载任务
+        for task in tasks {
+            tx.send(task)?;
+        }
+        drop(tx);
+
+        // 等待所有工作线程完成
+        for handle in handles {
+            handle.join().map_err(|_| TransferError::ThreadJoinError)?;
+        }
+
+        progress.finish();
+        Ok(())
+    }
+
+    fn download_file_worker(client: &SshClient, sftp: &Sftp, task: &DownloadTask, config: &Config, limiter: &RateLimiter, resume: bool) -> Result<()> {
+        // 本地已有旧版本时先尝试增量下载
+        if config.delta && task.effective_size == task.size && task.local_path.is_file()
+            && Self::try_delta_download(client, &task.remote_path, &task.local_path, limiter.for_file(), None).is_some()
+        {
+            return Ok(());
+        }
+
+        // 根据断点记录确定续传位置，并准备本地临时文件
+        let (journal, offset) = Self::open_sequential_journal(
+            config, &task.remote_path, &task.local_path, task.size, task.mtime, resume,
+        )?;
+        let local_file = Self::open_local_at(&part_path(&task.local_path), offset)?;
+        if config.preallocate && !config.sparse {
+            space::preallocate(&local_file, task.size);
+        }
+        let mut local_file = SparseWriter::new(local_file, config.sparse && offset == 0)?;
         
         // 打开远程文件并设置偏移量
         let mut remote_file = sftp.open(Path::new(&task.remote_path))?;
-        if task.offset > 0 {
-            remote_file.seek(SeekFrom::Start(task.offset))?;
+        if offset > 0 {
+            remote_file.seek(SeekFrom::Start(offset))?;
         }
     
         // 对于大文件使用更大的缓冲区
@@ -381,6 +1157,8 @@ impl Downloader {
         };
         
         let mut buffer = vec![0u8; buffer_size];
+        let mut cursor = journal.as_ref().map(|j| ChunkCursor::new(j, config.chunk_size as u64, offset));
+        let throttle = limiter.for_file();
         
         // 添加进度反馈
         let mut bytes_downloaded = 0;
@@ -396,7 +1174,11 @@ impl Downloader {
             match remote_file.read(&mut buffer) {
                 Ok(0) => break, // EOF
                 Ok(bytes_read) => {
+                    throttle.consume(bytes_read);
                     local_file.write_all(&buffer[..bytes_read])?;
+                    if let Some(cursor) = cursor.as_mut() {
+                        cursor.advance(&buffer[..bytes_read])?;
+                    }
                     bytes_downloaded += bytes_read as u64;
                     
                     // 减少进度更新频率
@@ -405,12 +1187,21 @@ impl Downloader {
                         bytes_downloaded = 0;
                     }
                 }
-                Err(e) => return Err(e.into()),
+                Err(e) => {
+                    if let Some(journal) = &journal {
+                        journal.flush()?;
+                    }
+                    return Err(e.into());
+                }
             }
         }
     
         // 确保数据写入磁盘
-        local_file.flush()?;
+        local_file.finish()?;
+        if let Some(journal) = &journal {
+            journal.flush()?;
+            journal.finish()?;
+        }
         Ok(())
     }
 }
@@ -419,6 +1210,15 @@ impl Downloader {
 struct DownloadTask {
     remote_path: String,
     local_path: PathBuf,
-    offset: u64,         // 断点续传的起始位置
+    size: u64,           // 远程文件大小
+    mtime: u64,          // 远程文件修改时间
     effective_size: u64,  // 实际需要下载的大小
-}
\ No newline at end of file
+    attributes: FileAttributes,  // 需要保留的远程文件属性
+}
+
+#[derive(Debug)]
+struct ChunkTask {
+    chunk_id: usize,
+    start: u64,
+    end: u64,           // 包含结束位置
+}
@@ -0,0 +1,172 @@
+// 递归传输时的符号链接和硬链接处理
+use crate::ssh::client::shell_quote;
+use crate::ssh::SshClient;
+use anyhow::{bail, Result};
+use ssh2::Sftp;
+use std::collections::HashMap;
+use std::fs::Metadata;
+use std::io::Read;
+use std::path::Path;
+use std::sync::Mutex;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum LinkPolicy {
+    // 在目标端重新创建符号链接
+    Copy,
+    // 复制链接指向的内容
+    #[default]
+    Follow,
+    // 跳过符号链接
+    Skip,
+}
+
+// 在本地创建指向 target 的符号链接，已存在的文件或链接会被替换
+pub fn create_local_symlink(target: &Path, link: &Path) -> Result<()> {
+    if std::fs::symlink_metadata(link).is_ok_and(|metadata| !metadata.is_dir()) {
+        std::fs::remove_file(link)?;
+    }
+
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(target, link)?;
+        Ok(())
+    }
+    #[cfg(not(unix))]
+    {
+        anyhow::bail!("Creating symlinks is not supported on this platform: {}", link.display())
+    }
+}
+
+// 在远程创建指向 target 的符号链接，已存在的文件或链接会被替换
+pub fn create_remote_symlink(sftp: &Sftp, target: &Path, link: &str) -> Result<()> {
+    if sftp.lstat(Path::new(link)).is_ok_and(|stat| !stat.is_dir()) {
+        sftp.unlink(Path::new(link))?;
+    }
+    sftp.symlink(target, Path::new(link))?;
+    Ok(())
+}
+
+// 源文件的设备号和 inode 号，相同时说明是同一文件的多个硬链接
+pub type InodeKey = (u64, u64);
+
+// 只有链接数大于 1 的普通文件才需要检测
+pub fn local_inode(metadata: &Metadata) -> Option<InodeKey> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        (metadata.is_file() && metadata.nlink() > 1).then(|| (metadata.dev(), metadata.ino()))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = metadata;
+        None
+    }
+}
+
+// SFTP 不返回 inode，通过远程 find 一次列出目录树中链接数大于 1 的文件，需要 GNU find
+pub fn remote_inodes(client: &SshClient, remote_dir: &str) -> Result<HashMap<String, InodeKey>> {
+    let root = match remote_dir.trim_end_matches('/') {
+        "" => "/",
+        root => root,
+    };
+    let command = format!("find {} -type f -links +1 -printf '%D %i %p\\0'", shell_quote(root));
+    let mut channel = client.exec_channel(&command)?;
+    let mut output = Vec::new();
+    channel.read_to_end(&mut output)?;
+    channel.wait_close()?;
+    // 部分子目录不可读时 find 也会返回非零，只要有输出就使用
+    let status = channel.exit_status()?;
+    if status != 0 && output.is_empty() {
+        let mut stderr = String::new();
+        let _ = channel.stderr().read_to_string(&mut stderr);
+        bail!("remote find exited with status {}{}", status,
+              stderr.lines().last().map(|line| format!(": {}", line)).unwrap_or_default());
+    }
+
+    let mut inodes = HashMap::new();
+    for record in output.split(|&byte| byte == 0).filter(|record| !record.is_empty()) {
+        let record = String::from_utf8_lossy(record);
+        let mut fields = record.splitn(3, ' ');
+        let (Some(dev), Some(ino), Some(path)) = (fields.next(), fields.next(), fields.next()) else {
+            continue;
+        };
+        if let (Ok(dev), Ok(ino)) = (dev.parse(), ino.parse()) {
+            inodes.insert(path.to_string(), (dev, ino));
+        }
+    }
+    Ok(inodes)
+}
+
+// 记录每个 inode 第一次传输到的目标路径，之后遇到的其他名字在传输结束后创建为硬链接
+pub struct HardLinkTracker<T> {
+    enabled: bool,
+    first: Mutex<HashMap<InodeKey, T>>,
+    pending: Mutex<Vec<(T, T)>>,
+}
+
+impl<T: Clone> HardLinkTracker<T> {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            first: Mutex::new(HashMap::new()),
+            pending: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    // 返回 true 表示同一 inode 的内容已经传输过，destination 改为稍后创建的硬链接
+    pub fn register(&self, key: Option<InodeKey>, destination: &T) -> bool {
+        let Some(key) = key.filter(|_| self.enabled) else {
+            return false;
+        };
+        let mut first = self.first.lock().unwrap();
+        match first.get(&key) {
+            Some(existing) => {
+                self.pending.lock().unwrap().push((existing.clone(), destination.clone()));
+                true
+            }
+            None => {
+                first.insert(key, destination.clone());
+                false
+            }
+        }
+    }
+
+    // 目标已是最新、不需要传输的文件也可以作为其他名字的链接目标
+    pub fn remember(&self, key: Option<InodeKey>, destination: &T) {
+        if let Some(key) = key.filter(|_| self.enabled) {
+            self.first.lock().unwrap().entry(key).or_insert_with(|| destination.clone());
+        }
+    }
+
+    // 取出待创建的 (已有文件, 新链接) 列表
+    pub fn take_pending(&self) -> Vec<(T, T)> {
+        std::mem::take(&mut *self.pending.lock().unwrap())
+    }
+}
+
+// 在本地为 target 创建硬链接，已存在的文件会被替换
+pub fn create_local_hard_link(target: &Path, link: &Path) -> Result<()> {
+    if std::fs::symlink_metadata(link).is_ok_and(|metadata| !metadata.is_dir()) {
+        std::fs::remove_file(link)?;
+    }
+    std::fs::hard_link(target, link)?;
+    Ok(())
+}
+
+// 在远程通过 ln 为 target 创建硬链接，已存在的文件会被替换
+pub fn create_remote_hard_link(client: &SshClient, target: &str, link: &str) -> Result<()> {
+    let mut channel = client.exec_channel(&format!("ln -f -- {} {}", shell_quote(target), shell_quote(link)))?;
+    let mut stderr = String::new();
+    channel.stderr().read_to_string(&mut stderr)?;
+    channel.wait_close()?;
+    let status = channel.exit_status()?;
+    if status != 0 {
+        bail!("ln exited with status {}{}", status,
+              stderr.lines().last().map(|line| format!(": {}", line)).unwrap_or_default());
+    }
+    Ok(())
+}
@@ -3,6 +3,16 @@ pub mod download;
 pub mod upload;
 pub mod resume;
 pub mod progress;
+pub mod conflict;
+pub mod summary;
+pub mod verify;
+pub mod delta;
+pub mod sync;
+pub mod plan;
+pub mod attributes;
+pub mod links;
+pub mod archive;
+pub mod compression;
 
 pub use download::Downloader;
 pub use upload::Uploader;
\ No newline at end of file
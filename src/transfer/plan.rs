@@ -0,0 +1,150 @@
+// 试运行时记录的传输计划
+use std::sync::Mutex;
+
+#[derive(Debug, Clone)]
+pub enum PlannedAction {
+    CreateDir { path: String },
+    Copy { source: String, target: String, size: u64 },
+    Resume { source: String, target: String, offset: u64, size: u64 },
+    Prompt { source: String, target: String, size: u64 },
+    Skip { path: String, reason: String },
+    Delete { path: String },
+    Symlink { path: String, target: String },
+    HardLink { path: String, target: String },
+}
+
+// 未启用试运行时所有记录操作都直接忽略
+pub struct TransferPlan {
+    enabled: bool,
+    actions: Mutex<Vec<PlannedAction>>,
+}
+
+impl TransferPlan {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            actions: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn push(&self, action: PlannedAction) {
+        if self.enabled {
+            self.actions.lock().unwrap().push(action);
+        }
+    }
+
+    pub fn create_dir(&self, path: impl ToString) {
+        if !self.enabled {
+            return;
+        }
+        let path = path.to_string();
+        let mut actions = self.actions.lock().unwrap();
+        // 多个文件共用的父目录只记录一次
+        if !actions.iter().any(|action| matches!(action, PlannedAction::CreateDir { path: p } if *p == path)) {
+            actions.push(PlannedAction::CreateDir { path });
+        }
+    }
+
+    pub fn copy(&self, source: impl ToString, target: impl ToString, size: u64) {
+        self.push(PlannedAction::Copy { source: source.to_string(), target: target.to_string(), size });
+    }
+
+    // 从 offset 处续传，offset 为 0 时按完整复制记录
+    pub fn transfer(&self, source: impl ToString, target: impl ToString, offset: u64, size: u64) {
+        if offset == 0 {
+            self.copy(source, target, size);
+        } else {
+            self.push(PlannedAction::Resume { source: source.to_string(), target: target.to_string(), offset, size });
+        }
+    }
+
+    pub fn prompt(&self, source: impl ToString, target: impl ToString, size: u64) {
+        self.push(PlannedAction::Prompt { source: source.to_string(), target: target.to_string(), size });
+    }
+
+    pub fn skip(&self, path: impl ToString, reason: &str) {
+        self.push(PlannedAction::Skip { path: path.to_string(), reason: reason.to_string() });
+    }
+
+    pub fn delete(&self, path: impl ToString) {
+        self.push(PlannedAction::Delete { path: path.to_string() });
+    }
+
+    pub fn symlink(&self, path: impl ToString, target: impl ToString) {
+        self.push(PlannedAction::Symlink { path: path.to_string(), target: target.to_string() });
+    }
+
+    pub fn hard_link(&self, path: impl ToString, target: impl ToString) {
+        self.push(PlannedAction::HardLink { path: path.to_string(), target: target.to_string() });
+    }
+
+    pub fn print(&self) {
+        let actions = self.actions.lock().unwrap();
+        let mut copied = 0u64;
+        let mut resumed = 0u64;
+        let mut bytes = 0u64;
+        let mut skipped = 0u64;
+        let mut created = 0u64;
+        let mut deleted = 0u64;
+        let mut prompts = 0u64;
+        let mut links = 0u64;
+        let mut hard_links = 0u64;
+
+        println!("\n=== Transfer plan (dry run) ===");
+        for action in actions.iter() {
+            match action {
+                PlannedAction::CreateDir { path } => {
+                    created += 1;
+                    println!("  create dir  {}", path);
+                }
+                PlannedAction::Copy { source, target, size } => {
+                    copied += 1;
+                    bytes += size;
+                    println!("  copy        {} -> {} ({} bytes)", source, target, size);
+                }
+                PlannedAction::Resume { source, target, offset, size } => {
+                    resumed += 1;
+                    bytes += size - offset;
+                    println!("  resume      {} -> {} from offset {} ({} of {} bytes remaining)",
+                             source, target, offset, size - offset, size);
+                }
+                PlannedAction::Prompt { source, target, size } => {
+                    prompts += 1;
+                    println!("  ask         {} -> {} ({} bytes, target exists)", source, target, size);
+                }
+                PlannedAction::Skip { path, reason } => {
+                    skipped += 1;
+                    println!("  skip        {} ({})", path, reason);
+                }
+                PlannedAction::Delete { path } => {
+                    deleted += 1;
+                    println!("  delete      {}", path);
+                }
+                PlannedAction::Symlink { path, target } => {
+                    links += 1;
+                    println!("  symlink     {} -> {}", path, target);
+                }
+                PlannedAction::HardLink { path, target } => {
+                    hard_links += 1;
+                    println!("  hard link   {} => {}", path, target);
+                }
+            }
+        }
+
+        println!("\nWould transfer {} bytes: {} full copy(ies), {} resumed", bytes, copied, resumed);
+        println!("Would skip {} file(s), create {} director(ies), delete {} entr(ies)", skipped, created, deleted);
+        if links > 0 {
+            println!("Would create {} symlink(s)", links);
+        }
+        if hard_links > 0 {
+            println!("Would create {} hard link(s) instead of copying", hard_links);
+        }
+        if prompts > 0 {
+            println!("{} existing file(s) would require a decision (--on-conflict prompt)", prompts);
+        }
+    }
+}
@@ -1,5 +1,5 @@
 // 进度显示
-use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use indicatif::{HumanBytes, MultiProgress, ProgressBar, ProgressStyle};
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
@@ -8,7 +8,7 @@ use once_cell::sync::Lazy;
 use std::path::Path;
 
 // 使用全局MultiProgress实例来管理所有进度条
-static MULTI_PROGRESS: Lazy<MultiProgress> = Lazy::new(|| MultiProgress::new());
+static MULTI_PROGRESS: Lazy<MultiProgress> = Lazy::new(MultiProgress::new);
 
 #[derive(Clone)]
 pub struct ProgressTracker {
@@ -18,6 +18,7 @@ pub struct ProgressTracker {
     last_update_time: Arc<Mutex<Instant>>,
     last_bytes: Arc<AtomicU64>,
     current_file: Arc<Mutex<String>>,  // 当前文件名
+    wire_bytes: Arc<AtomicU64>,  // 压缩流实际经过网络的字节数，未压缩时为0
 }
 
 impl ProgressTracker {
@@ -45,6 +46,7 @@ impl ProgressTracker {
             last_update_time: Arc::new(Mutex::new(Instant::now())),
             last_bytes: Arc::new(AtomicU64::new(0)),
             current_file: Arc::new(Mutex::new(String::new())),
+            wire_bytes: Arc::new(AtomicU64::new(0)),
         }
     }
 
@@ -59,6 +61,21 @@ impl ProgressTracker {
         }
     }
 
+    // 压缩传输时由流包装器累计网络字节数，进度同时显示原始字节数和网络字节数
+    pub fn wire_bytes(&self) -> Arc<AtomicU64> {
+        self.wire_bytes.clone()
+    }
+
+    // 有网络字节数时附加 "raw X, wire Y" 说明
+    fn wire_suffix(&self, raw: u64) -> String {
+        let wire = self.wire_bytes.load(Ordering::Relaxed);
+        if wire == 0 {
+            String::new()
+        } else {
+            format!(", raw {}, wire {}", HumanBytes(raw), HumanBytes(wire))
+        }
+    }
+
     pub fn update(&self, bytes_transferred: u64) {
         self.transferred_bytes.store(bytes_transferred, Ordering::Relaxed);
         self.progress_bar.set_position(bytes_transferred);
@@ -78,6 +95,17 @@ impl ProgressTracker {
         }
     }
 
+    // 撤回已计入的字节，用于失败后会重新传输的数据
+    fn remove_bytes(&self, bytes: u64) {
+        let current = self.transferred_bytes.fetch_sub(bytes, Ordering::Relaxed);
+        self.progress_bar.set_position(current.saturating_sub(bytes));
+    }
+
+    // 开始一次可能失败重试的传输，计入的字节在 commit 之前都可以撤回
+    pub fn attempt(&self) -> AttemptProgress<'_> {
+        AttemptProgress { progress: self, bytes: 0 }
+    }
+
     pub fn finish(&self) {
         // 计算平均速度
         let elapsed = self.start_time.elapsed();
@@ -94,7 +122,7 @@ impl ProgressTracker {
         let speed_str = format_speed(avg_speed);
         
         // 完成时显示传输完成信息
-        self.progress_bar.finish_with_message(format!("Transfer completed (avg speed: {})", speed_str));
+        self.progress_bar.finish_with_message(format!("Transfer completed (avg speed: {}{})", speed_str, self.wire_suffix(total)));
     }
 
     pub fn finish_with_error(&self, error: &str) {
@@ -123,7 +151,7 @@ impl ProgressTracker {
                 
                 // 格式化并显示速度
                 let speed_str = format_speed(speed);
-                self.progress_bar.set_message(speed_str);
+                self.progress_bar.set_message(format!("{}{}", speed_str, self.wire_suffix(current_bytes)));
                 
                 // 更新记录的时间和字节数
                 *last_time = now;
@@ -133,14 +161,37 @@ impl ProgressTracker {
     }
 }
 
+// 一次传输尝试计入的进度：没有 commit 就被丢弃（尝试失败）时撤回已计入的字节，重试时不会重复计数
+pub struct AttemptProgress<'a> {
+    progress: &'a ProgressTracker,
+    bytes: u64,
+}
+
+impl AttemptProgress<'_> {
+    pub fn add_bytes(&mut self, bytes: u64) {
+        self.bytes += bytes;
+        self.progress.add_bytes(bytes);
+    }
+
+    pub fn commit(mut self) {
+        self.bytes = 0;
+    }
+}
+
+impl Drop for AttemptProgress<'_> {
+    fn drop(&mut self) {
+        if self.bytes > 0 {
+            self.progress.remove_bytes(self.bytes);
+        }
+    }
+}
+
 // 从路径或描述中提取最后一部分作为前缀
 fn extract_last_part(path_or_description: &str) -> String {
     // 首先处理常见的前缀模式
-    if path_or_description.starts_with("Uploading from ") {
-        let path = &path_or_description["Uploading from ".len()..];
+    if let Some(path) = path_or_description.strip_prefix("Uploading from ") {
         return extract_last_part_from_path(path);
-    } else if path_or_description.starts_with("Downloading from ") {
-        let path = &path_or_description["Downloading from ".len()..];
+    } else if let Some(path) = path_or_description.strip_prefix("Downloading from ") {
         return extract_last_part_from_path(path);
     }
     
@@ -1,14 +1,28 @@
 // 断点续传
+use crate::utils::file::{HashAlgorithm, Hasher};
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
-use std::fs;
-use std::path::Path;
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// 断点记录落盘的最小间隔，避免大文件的每个分块都重写一次记录
+const SAVE_INTERVAL: Duration = Duration::from_secs(1);
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct ResumeInfo {
     pub file_path: String,
     pub total_size: u64,
+    // 源文件的修改时间（Unix 秒），用于检测两次运行之间源文件是否变化
+    #[serde(default)]
+    pub source_mtime: u64,
+    // 分块校验和使用的摘要算法
+    #[serde(default)]
+    pub hash_algorithm: HashAlgorithm,
     pub transferred_size: u64,
     pub chunks: HashMap<usize, ChunkInfo>,
 }
@@ -26,16 +40,39 @@ impl ResumeInfo {
         Self {
             file_path,
             total_size,
+            source_mtime: 0,
+            hash_algorithm: HashAlgorithm::default(),
             transferred_size: 0,
             chunks: HashMap::new(),
         }
     }
 
+    // 为源文件创建新的断点记录并规划分块
+    pub fn for_source(
+        file_path: &str,
+        total_size: u64,
+        source_mtime: u64,
+        chunk_size: u64,
+        hash_algorithm: HashAlgorithm,
+    ) -> Self {
+        let mut info = Self::new(file_path.to_string(), total_size);
+        info.source_mtime = source_mtime;
+        info.hash_algorithm = hash_algorithm;
+        info.plan_chunks(chunk_size);
+        info
+    }
+
+    // 先写入临时文件再重命名，保证崩溃时记录文件要么是旧的要么是新的
     pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
         let json = serde_json::to_string_pretty(self)
             .context("Failed to serialize resume info")?;
-        fs::write(path, json)
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        fs::write(&tmp_path, json)
             .context("Failed to write resume file")?;
+        fs::rename(&tmp_path, path)
+            .context("Failed to replace resume file")?;
         Ok(())
     }
 
@@ -47,19 +84,92 @@ impl ResumeInfo {
         Ok(resume_info)
     }
 
+    // 读取断点记录，只有与当前源文件一致时才返回
+    pub fn load_matching<P: AsRef<Path>>(
+        path: P,
+        file_path: &str,
+        total_size: u64,
+        source_mtime: u64,
+        chunk_size: u64,
+    ) -> Option<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return None;
+        }
+        Self::load_from_file(path)
+            .ok()
+            .filter(|info| info.matches(file_path, total_size, source_mtime, chunk_size))
+    }
+
     pub fn get_incomplete_chunks(&self) -> Vec<(usize, &ChunkInfo)> {
-        self.chunks
+        let mut chunks: Vec<(usize, &ChunkInfo)> = self.chunks
             .iter()
             .filter(|(_, chunk)| !chunk.completed)
             .map(|(id, chunk)| (*id, chunk))
-            .collect()
+            .collect();
+        // 按分块顺序返回，尽量保持顺序读写
+        chunks.sort_by_key(|(id, _)| *id);
+        chunks
+    }
+
+    // 按固定大小把整个文件划分为分块
+    pub fn plan_chunks(&mut self, chunk_size: u64) {
+        let chunk_size = chunk_size.max(1);
+        let mut start = 0;
+        let mut chunk_id = 0;
+        while start < self.total_size {
+            let end = (start + chunk_size).min(self.total_size) - 1;
+            self.add_chunk(chunk_id, start, end);
+            start = end + 1;
+            chunk_id += 1;
+        }
+    }
+
+    // 检查已有记录是否对应同一个未变化的源文件和相同的分块划分
+    pub fn matches(&self, file_path: &str, total_size: u64, source_mtime: u64, chunk_size: u64) -> bool {
+        self.file_path == file_path
+            && self.total_size == total_size
+            && self.source_mtime == source_mtime
+            && self.chunks.get(&0).map(|chunk| chunk.end - chunk.start + 1)
+                == Some(chunk_size.min(total_size))
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.chunks.values().all(|chunk| chunk.completed)
+    }
+
+    // 从文件开头起连续完成的字节数，顺序传输从这里继续
+    pub fn completed_prefix(&self) -> u64 {
+        let mut offset = 0;
+        let mut chunk_id = 0;
+        while let Some(chunk) = self.chunks.get(&chunk_id) {
+            if !chunk.completed {
+                break;
+            }
+            offset = chunk.end + 1;
+            chunk_id += 1;
+        }
+        offset
     }
 
     pub fn mark_chunk_completed(&mut self, chunk_id: usize, checksum: Option<String>) {
         if let Some(chunk) = self.chunks.get_mut(&chunk_id) {
+            if !chunk.completed {
+                self.transferred_size += chunk.end - chunk.start + 1;
+            }
             chunk.completed = true;
             chunk.checksum = checksum;
-            self.transferred_size += chunk.end - chunk.start + 1;
+        }
+    }
+
+    // 将已完成的分块重新标记为未完成
+    pub fn invalidate_chunk(&mut self, chunk_id: usize) {
+        if let Some(chunk) = self.chunks.get_mut(&chunk_id) {
+            if chunk.completed {
+                self.transferred_size -= chunk.end - chunk.start + 1;
+            }
+            chunk.completed = false;
+            chunk.checksum = None;
         }
     }
 
@@ -72,11 +182,351 @@ impl ResumeInfo {
         });
     }
 
+    // 上传的断点记录保存在用户状态目录中，按用户、主机、端口和远程路径区分，不写入源目录
+    // 目录为 $XDG_STATE_HOME/ssh-transfer/uploads，未设置时为 ~/.local/state/ssh-transfer/uploads
+    pub fn upload_journal_path(username: &str, host: &str, port: u16, remote_path: &str) -> Result<PathBuf> {
+        let state_dir = match std::env::var_os("XDG_STATE_HOME").filter(|dir| !dir.is_empty()) {
+            Some(dir) => PathBuf::from(dir),
+            None => home::home_dir().context("Cannot determine home directory")?.join(".local").join("state"),
+        };
+        let dir = state_dir.join("ssh-transfer").join("uploads");
+        let key = format!("{}@{}:{}:{}", username, host, port, remote_path);
+        let digest = Sha256::digest(key.as_bytes());
+        Ok(dir.join(format!("{}.resume", hex::encode(&digest[..16]))))
+    }
+
     pub fn resume_file_path<P: AsRef<Path>>(file_path: P) -> std::path::PathBuf {
         let path = file_path.as_ref();
         let mut resume_path = path.to_path_buf();
-        resume_path.set_extension(format!("{}.resume", 
+        resume_path.set_extension(format!("{}.resume",
             path.extension().and_then(|s| s.to_str()).unwrap_or("tmp")));
         resume_path
     }
-}
\ No newline at end of file
+}
+
+// 多个工作线程共享的断点记录，分块完成后定期写入磁盘
+// path 为 None 时只在内存中记录，不写入磁盘
+pub struct ResumeJournal {
+    info: Mutex<ResumeInfo>,
+    path: Option<PathBuf>,
+    last_saved: Mutex<Instant>,
+}
+
+impl ResumeJournal {
+    pub fn new(info: ResumeInfo, path: Option<PathBuf>) -> Self {
+        Self {
+            info: Mutex::new(info),
+            path,
+            last_saved: Mutex::new(Instant::now()),
+        }
+    }
+
+    // 打开断点记录：启用续传且记录与源文件一致时沿用，否则重新开始
+    // 沿用的记录保留原有的摘要算法，返回值中的布尔值表示是否沿用了已有记录
+    pub fn open(
+        path: Option<PathBuf>,
+        file_path: &str,
+        total_size: u64,
+        source_mtime: u64,
+        chunk_size: u64,
+        hash_algorithm: HashAlgorithm,
+        resume: bool,
+    ) -> Result<(Self, bool)> {
+        let mut existing = None;
+        if let Some(path) = path.as_ref().filter(|path| resume && path.exists()) {
+            match ResumeInfo::load_from_file(path) {
+                Ok(info) if info.matches(file_path, total_size, source_mtime, chunk_size) => existing = Some(info),
+                Ok(_) => println!("Source changed since the interrupted transfer, restarting: {}", file_path),
+                Err(e) => println!("Ignoring unreadable resume file {}: {}", path.display(), e),
+            }
+        }
+
+        let resuming = existing.is_some();
+        let info = existing.unwrap_or_else(|| {
+            ResumeInfo::for_source(file_path, total_size, source_mtime, chunk_size, hash_algorithm)
+        });
+        if let Some(parent) = path.as_ref().and_then(|path| path.parent()) {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        let journal = Self::new(info, path);
+        journal.flush()?;
+        Ok((journal, resuming))
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    pub fn hash_algorithm(&self) -> HashAlgorithm {
+        self.info.lock().unwrap().hash_algorithm
+    }
+
+    pub fn transferred_size(&self) -> u64 {
+        self.info.lock().unwrap().transferred_size
+    }
+
+    pub fn completed_prefix(&self) -> u64 {
+        self.info.lock().unwrap().completed_prefix()
+    }
+
+    // 取出所有未完成分块的 (编号, 起始位置, 结束位置)
+    pub fn pending_chunks(&self) -> Vec<(usize, u64, u64)> {
+        self.info
+            .lock()
+            .unwrap()
+            .get_incomplete_chunks()
+            .into_iter()
+            .map(|(id, chunk)| (id, chunk.start, chunk.end))
+            .collect()
+    }
+
+    pub fn complete_chunk(&self, chunk_id: usize, checksum: Option<String>) -> Result<()> {
+        let mut info = self.info.lock().unwrap();
+        info.mark_chunk_completed(chunk_id, checksum);
+
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let mut last_saved = self.last_saved.lock().unwrap();
+        if last_saved.elapsed() >= SAVE_INTERVAL {
+            info.save_to_file(path)?;
+            *last_saved = Instant::now();
+        }
+        Ok(())
+    }
+
+    // 用记录的校验和检查本地已完成的分块，不一致的分块重新传输
+    // 返回被判定为损坏的分块数量
+    pub fn verify_local(&self, local_path: &Path) -> Result<usize> {
+        let mut info = self.info.lock().unwrap();
+        let mut file = match File::open(local_path) {
+            Ok(file) => file,
+            Err(_) => {
+                // 本地文件已不存在，全部重新传输
+                let ids: Vec<usize> = info.chunks.keys().copied().collect();
+                let count = ids.iter().filter(|id| info.chunks[id].completed).count();
+                for id in ids {
+                    info.invalidate_chunk(id);
+                }
+                return Ok(count);
+            }
+        };
+        let file_len = file.metadata()?.len();
+        let algorithm = info.hash_algorithm;
+
+        let mut corrupted = Vec::new();
+        let mut buffer = vec![0u8; 1024 * 1024];
+        for (id, chunk) in info.chunks.iter() {
+            if !chunk.completed {
+                continue;
+            }
+            let intact = match &chunk.checksum {
+                Some(expected) if chunk.end < file_len => {
+                    file.seek(SeekFrom::Start(chunk.start))?;
+                    let mut hasher = algorithm.hasher();
+                    let mut remaining = chunk.end - chunk.start + 1;
+                    while remaining > 0 {
+                        let want = (remaining as usize).min(buffer.len());
+                        file.read_exact(&mut buffer[..want])?;
+                        hasher.update(&buffer[..want]);
+                        remaining -= want as u64;
+                    }
+                    hasher.finalize() == *expected
+                }
+                Some(_) => false,
+                None => chunk.end < file_len,
+            };
+            if !intact {
+                corrupted.push(*id);
+            }
+        }
+
+        for id in &corrupted {
+            info.invalidate_chunk(*id);
+        }
+        Ok(corrupted.len())
+    }
+
+    // 立即把当前进度写入磁盘
+    pub fn flush(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        self.info.lock().unwrap().save_to_file(path)?;
+        *self.last_saved.lock().unwrap() = Instant::now();
+        Ok(())
+    }
+
+    // 所有分块完成后删除断点记录
+    pub fn finish(&self) -> Result<()> {
+        match &self.path {
+            Some(path) if self.info.lock().unwrap().is_complete() && path.exists() => {
+                fs::remove_file(path).context("Failed to remove resume file")
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+// 顺序传输时按分块边界计算校验和并记录完成进度
+pub struct ChunkCursor<'a> {
+    journal: &'a ResumeJournal,
+    chunk_size: u64,
+    total_size: u64,
+    offset: u64,
+    hash_algorithm: HashAlgorithm,
+    hasher: Box<dyn Hasher>,
+}
+
+impl<'a> ChunkCursor<'a> {
+    // offset 必须位于分块边界上，通常取自 completed_prefix
+    pub fn new(journal: &'a ResumeJournal, chunk_size: u64, offset: u64) -> Self {
+        let (total_size, hash_algorithm) = {
+            let info = journal.info.lock().unwrap();
+            (info.total_size, info.hash_algorithm)
+        };
+        Self {
+            journal,
+            chunk_size: chunk_size.max(1),
+            total_size,
+            offset,
+            hash_algorithm,
+            hasher: hash_algorithm.hasher(),
+        }
+    }
+
+    pub fn advance(&mut self, mut data: &[u8]) -> Result<()> {
+        while !data.is_empty() {
+            let chunk_end = ((self.offset / self.chunk_size + 1) * self.chunk_size).min(self.total_size);
+            let take = ((chunk_end - self.offset) as usize).min(data.len());
+            self.hasher.update(&data[..take]);
+            self.offset += take as u64;
+            data = &data[take..];
+
+            if self.offset == chunk_end {
+                let hasher = std::mem::replace(&mut self.hasher, self.hash_algorithm.hasher());
+                let chunk_id = ((chunk_end - 1) / self.chunk_size) as usize;
+                self.journal.complete_chunk(chunk_id, Some(hasher.finalize()))?;
+            }
+            if take == 0 {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("ssh-transfer-resume-{}-{}", std::process::id(), name))
+    }
+
+    fn checksum(algorithm: HashAlgorithm, data: &[u8]) -> String {
+        let mut hasher = algorithm.hasher();
+        hasher.update(data);
+        hasher.finalize()
+    }
+
+    #[test]
+    fn changed_source_rejects_journal() {
+        let info = ResumeInfo::for_source("/data/file.bin", 10_000, 1_700_000_000, 4096, HashAlgorithm::Md5);
+        assert!(info.matches("/data/file.bin", 10_000, 1_700_000_000, 4096));
+        assert!(!info.matches("/data/other.bin", 10_000, 1_700_000_000, 4096));
+        assert!(!info.matches("/data/file.bin", 10_001, 1_700_000_000, 4096));
+        assert!(!info.matches("/data/file.bin", 10_000, 1_700_000_001, 4096));
+        // 分块大小变化后旧记录的分块边界不再适用
+        assert!(!info.matches("/data/file.bin", 10_000, 1_700_000_000, 8192));
+
+        let path = temp_path("changed.resume");
+        info.save_to_file(&path).unwrap();
+        assert!(ResumeInfo::load_matching(&path, "/data/file.bin", 10_000, 1_700_000_000, 4096).is_some());
+        assert!(ResumeInfo::load_matching(&path, "/data/file.bin", 10_000, 1_700_000_099, 4096).is_none());
+        assert!(ResumeInfo::load_matching(&path, "/data/file.bin", 10_000, 1_700_000_000, 1024).is_none());
+        fs::remove_file(&path).unwrap();
+        assert!(ResumeInfo::load_matching(&path, "/data/file.bin", 10_000, 1_700_000_000, 4096).is_none());
+    }
+
+    #[test]
+    fn completed_prefix_stops_at_first_incomplete_chunk() {
+        let mut info = ResumeInfo::for_source("file", 10, 0, 3, HashAlgorithm::Md5);
+        assert_eq!(info.chunks.len(), 4);
+        assert_eq!(info.completed_prefix(), 0);
+
+        info.mark_chunk_completed(0, None);
+        info.mark_chunk_completed(2, None);
+        assert_eq!(info.completed_prefix(), 3);
+        assert_eq!(info.transferred_size, 6);
+
+        info.mark_chunk_completed(1, None);
+        info.mark_chunk_completed(3, None);
+        assert_eq!(info.completed_prefix(), 10);
+        assert!(info.is_complete());
+
+        info.invalidate_chunk(1);
+        assert_eq!(info.completed_prefix(), 3);
+        assert_eq!(info.transferred_size, 7);
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let mut info = ResumeInfo::for_source("/remote/file", 5000, 42, 1024, HashAlgorithm::Sha256);
+        info.mark_chunk_completed(0, Some("abc".to_string()));
+        info.mark_chunk_completed(1, Some("def".to_string()));
+
+        let path = temp_path("round-trip.resume");
+        info.save_to_file(&path).unwrap();
+        // 原子写入不会留下临时文件
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        assert!(!Path::new(&tmp_path).exists());
+
+        let loaded = ResumeInfo::load_from_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(loaded.matches("/remote/file", 5000, 42, 1024));
+        assert_eq!(loaded.hash_algorithm, HashAlgorithm::Sha256);
+        assert_eq!(loaded.transferred_size, 2048);
+        assert_eq!(loaded.completed_prefix(), 2048);
+        assert_eq!(loaded.chunks[&1].checksum.as_deref(), Some("def"));
+        assert_eq!(loaded.get_incomplete_chunks().len(), 3);
+    }
+
+    #[test]
+    fn chunk_cursor_completes_chunks_on_boundaries() {
+        let data: Vec<u8> = (0..10u8).collect();
+        let info = ResumeInfo::for_source("file", data.len() as u64, 0, 4, HashAlgorithm::Md5);
+        let journal = ResumeJournal::new(info, None);
+
+        let mut cursor = ChunkCursor::new(&journal, 4, 0);
+        cursor.advance(&data[..3]).unwrap();
+        assert_eq!(journal.completed_prefix(), 0);
+        // 一次写入跨越分块边界
+        cursor.advance(&data[3..9]).unwrap();
+        assert_eq!(journal.completed_prefix(), 8);
+        cursor.advance(&data[9..]).unwrap();
+        assert_eq!(journal.completed_prefix(), 10);
+
+        let info = journal.info.lock().unwrap();
+        assert!(info.is_complete());
+        assert_eq!(info.chunks[&0].checksum, Some(checksum(HashAlgorithm::Md5, &data[..4])));
+        assert_eq!(info.chunks[&1].checksum, Some(checksum(HashAlgorithm::Md5, &data[4..8])));
+        assert_eq!(info.chunks[&2].checksum, Some(checksum(HashAlgorithm::Md5, &data[8..])));
+    }
+
+    #[test]
+    fn chunk_cursor_resumes_from_completed_prefix() {
+        let data = [7u8; 12];
+        let mut info = ResumeInfo::for_source("file", 12, 0, 4, HashAlgorithm::Md5);
+        info.mark_chunk_completed(0, Some(checksum(HashAlgorithm::Md5, &data[..4])));
+        let journal = ResumeJournal::new(info, None);
+
+        let offset = journal.completed_prefix();
+        let mut cursor = ChunkCursor::new(&journal, 4, offset);
+        cursor.advance(&data[offset as usize..]).unwrap();
+        assert_eq!(journal.completed_prefix(), 12);
+        assert!(journal.pending_chunks().is_empty());
+    }
+}
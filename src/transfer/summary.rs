@@ -0,0 +1,120 @@
+// 传输结果汇总
+use crate::utils::error::TransferError;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+#[derive(Default)]
+pub struct TransferSummary {
+    transferred_files: AtomicU64,
+    transferred_bytes: AtomicU64,
+    skipped_files: AtomicU64,
+    symlinks: AtomicU64,
+    hard_links: AtomicU64,
+    deleted_entries: AtomicU64,
+    excluded_entries: AtomicU64,
+    ignored_entries: AtomicU64,
+    verified_files: AtomicU64,
+    verify_failures: AtomicU64,
+    failures: Mutex<Vec<(String, String)>>,  // (文件路径, 错误信息)
+}
+
+impl TransferSummary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record_transferred(&self, bytes: u64) {
+        self.transferred_files.fetch_add(1, Ordering::Relaxed);
+        self.transferred_bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn record_skipped(&self) {
+        self.skipped_files.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_symlink(&self) {
+        self.symlinks.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_hard_link(&self) {
+        self.hard_links.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_deleted(&self) {
+        self.deleted_entries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_excluded(&self) {
+        self.excluded_entries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_ignored(&self) {
+        self.ignored_entries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_verified(&self) {
+        self.verified_files.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_failure(&self, path: &str, error: &anyhow::Error) {
+        if let Some(TransferError::VerificationFailed { .. }) = error.downcast_ref::<TransferError>() {
+            self.verify_failures.fetch_add(1, Ordering::Relaxed);
+        }
+        self.failures.lock().unwrap().push((path.to_string(), error.to_string()));
+    }
+
+    pub fn failed_count(&self) -> usize {
+        self.failures.lock().unwrap().len()
+    }
+
+    pub fn print(&self) {
+        println!("\n=== Transfer summary ===");
+        println!("Transferred: {} file(s), {} bytes",
+                 self.transferred_files.load(Ordering::Relaxed),
+                 self.transferred_bytes.load(Ordering::Relaxed));
+
+        let skipped = self.skipped_files.load(Ordering::Relaxed);
+        if skipped > 0 {
+            println!("Skipped:     {} file(s)", skipped);
+        }
+
+        let symlinks = self.symlinks.load(Ordering::Relaxed);
+        if symlinks > 0 {
+            println!("Symlinks:    {} created", symlinks);
+        }
+
+        let hard_links = self.hard_links.load(Ordering::Relaxed);
+        if hard_links > 0 {
+            println!("Hard links:  {} created", hard_links);
+        }
+
+        let excluded = self.excluded_entries.load(Ordering::Relaxed);
+        if excluded > 0 {
+            println!("Excluded:    {} entr{}", excluded, if excluded == 1 { "y" } else { "ies" });
+        }
+
+        let ignored = self.ignored_entries.load(Ordering::Relaxed);
+        if ignored > 0 {
+            println!("Ignored:     {} entr{} (gitignore)", ignored, if ignored == 1 { "y" } else { "ies" });
+        }
+
+        let deleted = self.deleted_entries.load(Ordering::Relaxed);
+        if deleted > 0 {
+            println!("Deleted:     {} entr{}", deleted, if deleted == 1 { "y" } else { "ies" });
+        }
+
+        let verified = self.verified_files.load(Ordering::Relaxed);
+        let verify_failures = self.verify_failures.load(Ordering::Relaxed);
+        if verified + verify_failures > 0 {
+            println!("Verified:    {} ok, {} mismatched", verified, verify_failures);
+        }
+
+        let failures = self.failures.lock().unwrap();
+        if !failures.is_empty() {
+            println!("Failed:      {} file(s)", failures.len());
+            for (path, error) in failures.iter() {
+                println!("  {}: {}", path, error);
+            }
+        }
+    }
+}
@@ -0,0 +1,79 @@
+// 目录同步：只复制有差异的文件，可选删除目标端多余的条目
+use crate::transfer::conflict::FileFacts;
+use crate::utils::file::is_part_file;
+use anyhow::Result;
+use ssh2::Sftp;
+use std::collections::HashMap;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum SyncDirection {
+    // 本地目录同步到远程
+    Push,
+    // 远程目录同步到本地
+    Pull,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SyncOptions {
+    pub delete: bool,
+    pub checksum: bool,
+}
+
+impl SyncOptions {
+    // 判断目标文件是否需要更新，contents_differ 只在启用 --checksum 且大小一致时调用
+    pub fn needs_copy<F>(&self, source: FileFacts, existing: Option<FileFacts>, contents_differ: F) -> Result<bool>
+    where
+        F: FnOnce() -> Result<bool>,
+    {
+        let existing = match existing {
+            Some(existing) => existing,
+            None => return Ok(true),
+        };
+        if source.size != existing.size {
+            return Ok(true);
+        }
+        if self.checksum {
+            return contents_differ();
+        }
+        Ok(source.mtime != existing.mtime)
+    }
+}
+
+// 找出目标目录中需要删除的条目：源目录中不存在，或者文件和目录类型不一致
+// 未完成传输的临时文件留给续传使用，不会删除
+pub fn extraneous<I>(source: &HashMap<String, bool>, destination: I) -> Vec<(String, bool)>
+where
+    I: IntoIterator<Item = (String, bool)>,
+{
+    let mut entries: Vec<(String, bool)> = destination
+        .into_iter()
+        .filter(|(name, _)| !is_part_file(name))
+        .filter(|(name, is_dir)| source.get(name) != Some(is_dir))
+        .collect();
+    entries.sort();
+    entries
+}
+
+// 删除本地文件或整个目录
+pub fn remove_local(path: &Path, is_dir: bool) -> Result<()> {
+    if is_dir {
+        std::fs::remove_dir_all(path)?;
+    } else {
+        std::fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+// 删除远程文件或递归删除远程目录
+pub fn remove_remote(sftp: &Sftp, path: &str, is_dir: bool) -> Result<()> {
+    if !is_dir {
+        sftp.unlink(Path::new(path))?;
+        return Ok(());
+    }
+    for (child, stat) in sftp.readdir(Path::new(path))? {
+        remove_remote(sftp, &child.to_string_lossy(), stat.is_dir())?;
+    }
+    sftp.rmdir(Path::new(path))?;
+    Ok(())
+}
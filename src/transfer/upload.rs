@@ -1,13 +1,29 @@
 // 上传功能
 use crate::config::Config;
-use crate::ssh::SshSession;
+use crate::ssh::{SshClient, SshSession};
+use crate::transfer::archive;
+use crate::transfer::delta::{self, DeltaStats};
+use crate::transfer::links::{self, HardLinkTracker, LinkPolicy};
+use crate::transfer::plan::TransferPlan;
+use crate::transfer::attributes::FileAttributes;
+use crate::transfer::conflict::{free_name, ConflictResolver, FileFacts, Resolution};
 use crate::transfer::progress::ProgressTracker;
-use crate::utils::error::TransferError;
+use crate::transfer::resume::{ChunkCursor, ResumeInfo, ResumeJournal};
+use crate::transfer::summary::TransferSummary;
+use crate::transfer::sync;
+use crate::transfer::verify::{remote_hash, verify_after_transfer};
+use crate::utils::error::{classify, TransferError};
+use crate::utils::filter::{join_relative, IgnoreStack};
+use crate::utils::file::{calculate_hash, mtime_secs, part_path, HashAlgorithm};
+use crate::utils::space;
+use crate::utils::rate::{RateLimiter, Throttle};
+use crate::utils::sparse::{SparseReader, SparseWriter};
 use anyhow::{Context, Result};
 use crossbeam_channel::{bounded, Receiver, Sender};
 use ssh2::{Sftp, OpenType};
-use std::fs::File;
-use std::io::{Read, Write, Seek, SeekFrom};
+use std::collections::HashMap;
+use std::fs::{File, Metadata};
+use std::io::{Read, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::thread;
@@ -18,6 +34,11 @@ pub struct Uploader {
     session: SshSession,
     config: Arc<Config>,
     remote_home: String,
+    summary: Arc<TransferSummary>,
+    conflicts: ConflictResolver,
+    plan: TransferPlan,
+    hard_links: HardLinkTracker<String>,
+    limiter: Arc<RateLimiter>,
 }
 
 impl Uploader {
@@ -27,12 +48,32 @@ impl Uploader {
         // 首先获取远程系统的家目录
         let remote_home = Self::detect_remote_home_dir(&session, &config.username)?;
         
+        let conflicts = ConflictResolver::new(config.on_conflict);
+        let plan = TransferPlan::new(config.dry_run);
+        let hard_links = HardLinkTracker::new(config.hard_links);
+        let limiter = Arc::new(RateLimiter::new(
+            config.limit_rate, config.limit_rate_per_file, config.bandwidth_schedule.clone(),
+        ));
+        
         Ok(Self {
             session,
             config: Arc::new(config),
             remote_home,
+            summary: Arc::new(TransferSummary::new()),
+            conflicts,
+            plan,
+            hard_links,
+            limiter,
         })
     }
+
+    pub fn summary(&self) -> &TransferSummary {
+        &self.summary
+    }
+
+    pub fn plan(&self) -> &TransferPlan {
+        &self.plan
+    }
     
     // 检测远程系统的家目录
     fn detect_remote_home_dir(session: &SshSession, username: &str) -> Result<String> {
@@ -67,7 +108,7 @@ impl Uploader {
 
         // 确保本地文件存在
         if !local_path.exists() {
-            return Err(anyhow::anyhow!("Local file or directory does not exist: {}", local_path.display()));
+            return Err(TransferError::FileNotFound { path: local_path.display().to_string() }.into());
         }
 
         // 检查常见路径错误 - 检测shell扩展的本地路径
@@ -90,7 +131,10 @@ impl Uploader {
                     .file_name()
                     .ok_or_else(|| anyhow::anyhow!("Cannot determine directory name from local path"))?;
                 let new_remote_path = format!("{}/{}", remote_path.trim_end_matches('/'), file_name.to_string_lossy());
-                self.upload_directory(&sftp, local_path, &new_remote_path).await
+                let ancestors = self.link_ancestors(local_path);
+                self.upload_directory(&sftp, local_path, &new_remote_path, "", &IgnoreStack::new(self.config.respect_gitignore, local_path), &ancestors).await?;
+                self.create_hard_links();
+                Ok(())
             } else {
                 Err(TransferError::DirectoryNotAllowed.into())
             }
@@ -98,10 +142,156 @@ impl Uploader {
             // 确定目标文件路径
             let target_path = self.get_target_file_path(&sftp, &remote_path, local_path)?;
             println!("Target file path: {}", target_path);
-            self.upload_file(&sftp, local_path, &target_path).await
+            let metadata = std::fs::metadata(local_path)?;
+            let file_size = metadata.len();
+
+            // 续传未完成的远程临时文件时不视为冲突
+            let resuming = self.config.resume && sftp.stat(Path::new(&remote_part_path(&target_path))).is_ok();
+            if self.plan.is_enabled() && !resuming && self.conflicts.is_interactive() && sftp.stat(Path::new(&target_path)).is_ok() {
+                self.plan.prompt(local_path.display(), &target_path, file_size);
+                return Ok(());
+            }
+            let target_path = if resuming {
+                target_path
+            } else {
+                match self.resolve_conflict(&sftp, local_path, &target_path, file_size, mtime_secs(&metadata))? {
+                    Some(path) => path,
+                    None => {
+                        println!("Skipping existing file: {}", target_path);
+                        self.plan.skip(&target_path, "target exists");
+                        self.summary.record_skipped();
+                        return Ok(());
+                    }
+                }
+            };
+
+            // 试运行只记录计划
+            let offset = if resuming { self.resume_offset(&target_path, file_size, mtime_secs(&metadata)) } else { 0 };
+            if self.plan.is_enabled() {
+                self.plan.transfer(local_path.display(), &target_path, offset, file_size);
+                return Ok(());
+            }
+
+            // 开始传输前确认远程空间足够，避免传输到一半磁盘写满
+            let available = space::remote_available(&self.session.client, &sftp, &target_path);
+            space::ensure_space(&target_path, file_size - offset, available)?;
+
+            // 远程已有旧版本时只上传变化的块
+            if self.config.delta && !resuming {
+                let progress = ProgressTracker::new(file_size, &format!("Uploading {}", local_path.file_name().unwrap_or_default().to_string_lossy()));
+                match Self::try_delta_upload(&self.session.client, &sftp, local_path, &target_path, self.limiter.for_file(), Some(&progress)) {
+                    Some(stats) => {
+                        progress.finish();
+                        println!("✅ Delta upload completed: {} ({} bytes transferred, {} bytes reused, {:.1}% saved)",
+                                 target_path, stats.literal_bytes, stats.matched_bytes, stats.saved_percent());
+                        return self.finish_file(&sftp, local_path, &target_path, file_size);
+                    }
+                    None => progress.finish_with_error("delta transfer not possible"),
+                }
+            }
+
+            // 临时错误按 --retries 重试，连接断开时重新连接，全部成功后再校验和提交
+            // 失败后再次上传时总是从断点记录确认的位置续传
+            let what = format!("Upload of {}", local_path.display());
+            let mut sftp = Some(sftp);
+            self.session.client.run_reconnecting(&self.config.retry, &what, &mut sftp, |sftp, failed| {
+                let resume = self.config.resume || failed;
+                if self.config.threads > 1 && file_size > self.config.chunk_size as u64 {
+                    // 大文件按分块并行上传
+                    self.upload_file_chunked(sftp, local_path, &target_path, file_size, mtime_secs(&metadata), resume)
+                } else {
+                    self.upload_file(sftp, local_path, &target_path, resume)
+                }
+            })
+            .map_err(|e| classify(e, local_path, &target_path))?;
+            let sftp = match sftp {
+                Some(sftp) => sftp,
+                None => self.session.client.sftp()?,
+            };
+            self.finish_file(&sftp, local_path, &target_path, file_size)
         }
     }
 
+    // 把本地目录同步到远程目录，只上传有差异的文件
+    pub async fn sync<P: AsRef<Path>>(&self, local_path: P, remote_path_str: &str) -> Result<()> {
+        let local_path = local_path.as_ref();
+        if !local_path.is_dir() {
+            return Err(anyhow::anyhow!("sync requires a local directory: {}", local_path.display()));
+        }
+
+        let sftp = self.session.client.sftp()?;
+        let remote_path = self.resolve_remote_path_str(remote_path_str);
+        println!("Resolved remote path: {}", remote_path);
+        let ancestors = self.link_ancestors(local_path);
+        self.upload_directory(&sftp, local_path, &remote_path, "", &IgnoreStack::new(self.config.respect_gitignore, local_path), &ancestors).await?;
+        self.create_hard_links();
+        Ok(())
+    }
+
+    // 所有文件上传完成后再在远程创建硬链接，保证链接目标已经写入
+    fn create_hard_links(&self) {
+        for (target, link) in self.hard_links.take_pending() {
+            if self.plan.is_enabled() {
+                self.plan.hard_link(&link, &target);
+                continue;
+            }
+            match links::create_remote_hard_link(&self.session.client, &target, &link) {
+                Ok(()) => {
+                    log::debug!("Hard linked {} => {}", link, target);
+                    self.summary.record_hard_link();
+                }
+                Err(e) => self.summary.record_failure(&link, &e),
+            }
+        }
+    }
+
+    // 跟随符号链接时记录根目录的真实路径，用于检测链接循环；其他模式不会进入链接目录
+    fn link_ancestors(&self, local_dir: &Path) -> Vec<PathBuf> {
+        if self.config.links != LinkPolicy::Follow {
+            return Vec::new();
+        }
+        vec![std::fs::canonicalize(local_dir).unwrap_or_else(|_| local_dir.to_path_buf())]
+    }
+
+    // 读取本地目录，不跟随符号链接；跟随模式下用链接目标的元数据替换链接本身的元数据
+    // 返回的标记表示条目是否为符号链接，悬空链接保留原有元数据
+    fn read_local_dir(&self, local_dir: &Path) -> Result<Vec<(PathBuf, Metadata, bool)>> {
+        let follow = self.config.links == LinkPolicy::Follow;
+        let mut entries = Vec::new();
+        for entry in std::fs::read_dir(local_dir)? {
+            let path = entry?.path();
+            let metadata = std::fs::symlink_metadata(&path)?;
+            if !metadata.file_type().is_symlink() {
+                entries.push((path, metadata, false));
+                continue;
+            }
+            match std::fs::metadata(&path) {
+                Ok(target) if follow => entries.push((path, target, true)),
+                _ => entries.push((path, metadata, true)),
+            }
+        }
+        Ok(entries)
+    }
+
+    // 在远程重新创建本地符号链接，链接目标保持原样不做转换
+    fn copy_symlink(&self, sftp: &Sftp, local_path: &Path, remote_path: &str) -> Result<()> {
+        let target = std::fs::read_link(local_path)?;
+        if sftp.readlink(Path::new(remote_path)).is_ok_and(|existing| existing == target) {
+            log::debug!("Up to date: {}", remote_path);
+            self.plan.skip(remote_path, "up to date");
+            self.summary.record_skipped();
+            return Ok(());
+        }
+        if self.plan.is_enabled() {
+            self.plan.symlink(remote_path, target.display());
+            return Ok(());
+        }
+        links::create_remote_symlink(sftp, &target, remote_path)?;
+        println!("Created symlink: {} -> {}", remote_path, target.display());
+        self.summary.record_symlink();
+        Ok(())
+    }
+
     // 解析远程路径字符串，处理波浪线等特殊字符
     fn resolve_remote_path_str(&self, remote_path: &str) -> String {
         // 检查路径是否看起来像本地扩展的主目录
@@ -210,6 +400,10 @@ impl Uploader {
         
         // 按从最高级到最低级的顺序创建目录
         for dir in dirs_to_create.iter().rev() {
+            if self.plan.is_enabled() {
+                self.plan.create_dir(dir.display());
+                continue;
+            }
             println!("Creating remote directory: {}", dir.display());
             match sftp.mkdir(dir, 0o755) {
                 Ok(_) => {},
@@ -231,9 +425,14 @@ impl Uploader {
         &'a self, 
         sftp: &'a Sftp, 
         local_dir: &'a Path, 
-        remote_dir: &'a str
+        remote_dir: &'a str,
+        relative: &'a str,
+        parent_ignores: &'a IgnoreStack,
+        ancestors: &'a [PathBuf],
     ) -> Pin<Box<dyn Future<Output = Result<()>> + 'a>> {
         Box::pin(async move {
+            let ignores = parent_ignores.descend(local_dir);
+
             // 确保远程目录存在
             self.ensure_remote_directory(sftp, Path::new(remote_dir))?;
 
@@ -242,134 +441,211 @@ impl Uploader {
             let mut dirs_to_upload = Vec::new();
             let mut total_size: u64 = 0;
 
+            let entries = self.read_local_dir(local_dir)?;
+
+            // 同步时先删除本地已不存在的远程条目，类型变化的条目随后重新上传
+            let sync_options = self.config.sync_options();
+            if sync_options.is_some_and(|options| options.delete) && sftp.stat(Path::new(remote_dir)).is_ok() {
+                self.delete_extraneous(sftp, &entries, local_dir, remote_dir, relative, &ignores)?;
+            }
+
             // 使用std::fs::read_dir收集文件但不立即递归处理子目录
-            for entry in std::fs::read_dir(local_dir)? {
-                let entry = entry?;
-                let path = entry.path();
-                let file_name = entry.file_name().to_string_lossy().to_string();
+            for (path, metadata, is_link) in entries {
+                let file_name = path.file_name()
+                    .ok_or_else(|| anyhow::anyhow!("Cannot determine file name"))?
+                    .to_string_lossy().to_string();
                 let remote_path = format!("{}/{}", remote_dir.trim_end_matches('/'), file_name);
 
-                if path.is_dir() {
-                    // 收集子目录，稍后处理
-                    dirs_to_upload.push((path.to_path_buf(), remote_path));
-                } else if path.is_file() {
-                    // 添加文件到上传列表
-                    let metadata = std::fs::metadata(&path)?;
-                    let size = metadata.len();
-                    
-                    // 如果启用断点续传，检查远程文件
-                    let mut effective_size = size;
-                    let mut offset = 0;
-                    if self.config.resume {
-                        match sftp.stat(Path::new(&remote_path)) {
-                            Ok(stat) => {
-                                let remote_size = stat.size.unwrap_or(0);
-                                if remote_size < size {
-                                    // 只上传剩余部分
-                                    offset = remote_size;
-                                    effective_size = size - remote_size;
-                                } else if remote_size == size {
-                                    // 文件已完成，跳过
-                                    println!("Skipping already uploaded file: {}", path.display());
-                                    continue;
-                                }
+                // 被 .gitignore 忽略或被排除的目录整棵跳过，不再进入
+                if ignores.is_ignored(&path, metadata.is_dir()) {
+                    log::debug!("Ignored: {}", path.display());
+                    self.plan.skip(path.display(), "ignored");
+                    self.summary.record_ignored();
+                    continue;
+                }
+                let entry_relative = join_relative(relative, &file_name);
+                if !self.config.filter.allows(&entry_relative, metadata.is_dir()) {
+                    log::debug!("Excluded: {}", path.display());
+                    self.plan.skip(path.display(), "excluded");
+                    self.summary.record_excluded();
+                    continue;
+                }
+
+                // 跟随模式下仍是链接元数据的条目是悬空链接
+                if metadata.file_type().is_symlink() {
+                    match self.config.links {
+                        LinkPolicy::Copy => {
+                            if let Err(e) = self.copy_symlink(sftp, &path, &remote_path) {
+                                self.summary.record_failure(&path.display().to_string(), &e);
                             }
-                            Err(_) => {} // 文件不存在，从头开始上传
+                        }
+                        LinkPolicy::Skip => {
+                            log::debug!("Skipping symlink: {}", path.display());
+                            self.plan.skip(path.display(), "symlink");
+                            self.summary.record_skipped();
+                        }
+                        LinkPolicy::Follow => {
+                            println!("Warning: skipping dangling symlink: {}", path.display());
+                            self.plan.skip(path.display(), "dangling symlink");
+                            self.summary.record_skipped();
                         }
                     }
-                    
-                    total_size += effective_size;
-                    files_to_upload.push((path, remote_path, offset, effective_size));
+                    continue;
                 }
-            }
 
-            // 为当前目录创建单个总进度条
-            let progress = Arc::new(ProgressTracker::new(
-                total_size, 
-                &format!("Uploading from {}", local_dir.display())
-            ));
+                if metadata.is_dir() {
+                    // 跟随链接进入已经在当前路径上的目录会无限递归
+                    let mut subdir_ancestors = ancestors.to_vec();
+                    if let Some(parent_real) = ancestors.last() {
+                        let real = if is_link { std::fs::canonicalize(&path)? } else { parent_real.join(&file_name) };
+                        if ancestors.contains(&real) {
+                            println!("Warning: skipping symlink loop: {} -> {}", path.display(), real.display());
+                            self.plan.skip(path.display(), "symlink loop");
+                            self.summary.record_skipped();
+                            continue;
+                        }
+                        subdir_ancestors.push(real);
+                    }
+                    // 收集子目录，稍后处理
+                    dirs_to_upload.push((path, remote_path, entry_relative, subdir_ancestors));
+                } else if metadata.is_file() {
+                    // 添加文件到上传列表
+                    let size = metadata.len();
+                    let mtime = mtime_secs(&metadata);
+                    let inode = if is_link { None } else { links::local_inode(&metadata) };
+                    
+                    // 如果启用断点续传，根据断点记录检查远程文件
+                    let offset = match self.planned_offset(sftp, &remote_path, size, mtime) {
+                        Some(offset) => offset,
+                        None => {
+                            // 文件已完成，跳过
+                            println!("Skipping already uploaded file: {}", path.display());
+                            self.plan.skip(&remote_path, "already uploaded");
+                            self.summary.record_skipped();
+                            continue;
+                        }
+                    };
 
-            // 创建上传任务
-            let (tx, rx): (Sender<UploadTask>, Receiver<UploadTask>) = bounded(100);
-
-            // 启动工作线程
-            let mut handles = Vec::new();
-            for _ in 0..self.config.threads {
-                let rx = rx.clone();
-                let session = self.session.clone_session()?;
-                let config = Arc::clone(&self.config);
-                let progress = Arc::clone(&progress);
-
-                let handle = thread::spawn(move || {
-                    let sftp = session.sftp().unwrap();
-                    while let Ok(task) = rx.recv() {
-                        if let Err(e) = Self::upload_file_worker(&sftp, &task, &config) {
-                            eprintln!("Upload error for {}: {}", task.local_path.display(), e);
-                        } else {
-                            progress.add_bytes(task.effective_size);
+                    // 从头上传时按冲突策略处理已存在的远程文件，同步模式只上传有差异的文件
+                    let remote_path = if offset > 0 {
+                        remote_path
+                    } else if let Some(options) = sync_options {
+                        if !self.sync_needs_copy(options, sftp, &path, &remote_path, size, mtime)? {
+                            self.hard_links.remember(inode, &remote_path);
+                            log::debug!("Up to date: {}", remote_path);
+                            self.plan.skip(&remote_path, "up to date");
+                            self.summary.record_skipped();
+                            continue;
+                        }
+                        remote_path
+                    } else if self.plan.is_enabled() && self.conflicts.is_interactive() && sftp.stat(Path::new(&remote_path)).is_ok() {
+                        self.plan.prompt(path.display(), &remote_path, size);
+                        continue;
+                    } else {
+                        match self.resolve_conflict(sftp, &path, &remote_path, size, mtime)? {
+                            Some(remote_path) => remote_path,
+                            None => {
+                                println!("Skipping existing file: {}", remote_path);
+                                self.plan.skip(&remote_path, "target exists");
+                                self.summary.record_skipped();
+                                continue;
+                            }
                         }
+                    };
+                    
+                    // 同一 inode 的内容只上传一次，其他名字稍后创建为硬链接
+                    if self.hard_links.register(inode, &remote_path) {
+                        log::debug!("Hard link: {}", path.display());
+                        continue;
                     }
-                });
-                handles.push(handle);
-            }
 
-            // 发送上传任务
-            for (local_path, remote_path, offset, effective_size) in files_to_upload {
-                let task = UploadTask {
-                    local_path,
-                    remote_path,
-                    offset,
-                    effective_size,
-                };
-                tx.send(task)?;
+                    total_size += size - offset;
+                    self.plan.transfer(path.display(), &remote_path, offset, size);
+                    files_to_upload.push(UploadTask {
+                        local_path: path,
+                        remote_path,
+                        size,
+                        mtime,
+                        effective_size: size - offset,
+                        attributes: FileAttributes::from_local(&metadata),
+                    });
+                }
             }
-            drop(tx);
 
-            // 等待所有工作线程完成
-            for handle in handles {
-                handle.join().map_err(|_| TransferError::ThreadJoinError)?;
+            // 试运行只记录计划，不启动传输
+            if !self.plan.is_enabled() {
+                // 开始传输前确认远程空间足够，避免传输到一半磁盘写满
+                if total_size > 0 {
+                    let available = space::remote_available(&self.session.client, sftp, remote_dir);
+                    space::ensure_space(remote_dir, total_size, available)?;
+                }
+                if self.config.archive_mode {
+                    self.run_upload_archive(sftp, local_dir, remote_dir, files_to_upload, total_size);
+                } else {
+                    self.run_upload_tasks(local_dir, files_to_upload, total_size)?;
+                }
             }
 
-            progress.finish();
-            
             // 当前目录处理完毕后，顺序处理子目录
             // 这样避免同时创建太多进度条
-            for (local_subdir, remote_subdir) in dirs_to_upload {
-                self.upload_directory(sftp, &local_subdir, &remote_subdir).await?;
+            for (local_subdir, remote_subdir, subdir_relative, subdir_ancestors) in dirs_to_upload {
+                self.upload_directory(sftp, &local_subdir, &remote_subdir, &subdir_relative, &ignores, &subdir_ancestors).await?;
+            }
+
+            // 目录内容全部写入后再应用目录属性，否则写入文件会改变目录的修改时间
+            if !self.plan.is_enabled() {
+                self.preserve_attributes(sftp, local_dir, remote_dir)?;
             }
 
             Ok(())
         })
     }
 
-    async fn upload_file(&self, sftp: &Sftp, local_path: &Path, remote_path: &str) -> Result<()> {
+    // 远程目标文件已存在时按冲突策略决定写入路径，返回 None 表示跳过
+    fn resolve_conflict(&self, sftp: &Sftp, local_path: &Path, remote_path: &str, size: u64, mtime: u64) -> Result<Option<String>> {
+        let stat = match sftp.stat(Path::new(remote_path)) {
+            Ok(stat) if !stat.is_dir() => stat,
+            _ => return Ok(Some(remote_path.to_string())),
+        };
+
+        let source = FileFacts { size, mtime };
+        let existing = FileFacts { size: stat.size.unwrap_or(0), mtime: stat.mtime.unwrap_or(0) };
+        let algorithm = self.config.hash_algorithm;
+        let resolution = self.conflicts.resolve(remote_path, source, existing, || {
+            let local = calculate_hash(local_path, algorithm)?;
+            let remote = remote_hash(&self.session.client, sftp, remote_path, algorithm)?;
+            Ok(local != remote)
+        })?;
+
+        Ok(match resolution {
+            Resolution::Overwrite => Some(remote_path.to_string()),
+            Resolution::Skip => None,
+            Resolution::Rename => {
+                let renamed = free_name(Path::new(remote_path), |candidate| sftp.stat(Path::new(candidate)).is_ok());
+                println!("Saving as {} to avoid overwriting {}", renamed, remote_path);
+                Some(renamed)
+            }
+        })
+    }
+
+    fn upload_file(&self, sftp: &Sftp, local_path: &Path, remote_path: &str, resume: bool) -> Result<()> {
         let metadata = std::fs::metadata(local_path)?;
         let file_size = metadata.len();
 
         println!("Uploading file: {} -> {} ({} bytes)", 
                  local_path.display(), remote_path, file_size);
 
-        // 断点续传逻辑：检查远程文件是否存在
-        let mut offset = 0;
         let progress = ProgressTracker::new(file_size, &format!("Uploading {}", local_path.display()));
-        
-        if self.config.resume {
-            // 检查远程文件是否存在
-            match sftp.stat(Path::new(remote_path)) {
-                Ok(stat) => {
-                    let remote_size = stat.size.unwrap_or(0);
-                    if remote_size <= file_size {
-                        offset = remote_size;
-                        println!("Resuming upload from offset: {} bytes", offset);
-                        progress.update(offset); // 更新进度条以显示已上传部分
-                    } else {
-                        println!("Remote file is larger than local file. Starting upload from beginning.");
-                    }
-                }
-                Err(_) => {
-                    // 远程文件不存在，从头开始上传
-                }
-            }
+
+        // 断点续传逻辑：根据断点记录确定远程临时文件的续传位置
+        let remote_part = remote_part_path(remote_path);
+        let remote_size = sftp.stat(Path::new(&remote_part)).ok().and_then(|stat| stat.size);
+        let (journal, offset) = Self::open_sequential_journal(
+            &self.config, remote_path, file_size, mtime_secs(&metadata), resume, remote_size,
+        )?;
+        if offset > 0 {
+            println!("Resuming upload from offset: {} bytes", offset);
+            progress.update(offset); // 更新进度条以显示已上传部分
         }
 
         // 确保远程目录存在
@@ -379,37 +655,42 @@ impl Uploader {
             }
         }
 
-        let mut local_file = File::open(local_path)
+        let local_file = File::open(local_path)
             .with_context(|| format!("Failed to open local file: {}", local_path.display()))?;
+        let mut local_file = SparseReader::new(local_file, self.config.sparse)?;
         
         // 如果断点续传，先定位到偏移位置
         if offset > 0 {
             local_file.seek(SeekFrom::Start(offset))?;
         }
         
-        // 创建或打开远程文件
-        let mut remote_file = if offset > 0 {
-            // 以追加模式打开文件
-            sftp.open_mode(
-                Path::new(remote_path), 
-                ssh2::OpenFlags::WRITE | ssh2::OpenFlags::APPEND, 
-                0o644,
-                OpenType::File
-            )?
-        } else {
-            // 创建新文件
-            sftp.create(Path::new(remote_path))?
-        };
+        // 创建或打开远程临时文件，上传完成后再重命名为目标文件
+        // 只有新建的临时文件可以跳过全零块，续传时跳过的位置可能残留旧数据
+        let remote_file = Self::open_remote_at(sftp, &remote_part, offset)?;
+        let mut remote_file = SparseWriter::new(remote_file, self.config.sparse && offset == 0)?;
 
         let mut buffer = vec![0u8; self.config.chunk_size];
         let mut total_transferred = offset;
+        let mut cursor = journal.as_ref().map(|j| ChunkCursor::new(j, self.config.chunk_size as u64, offset));
+        let throttle = self.limiter.for_file();
 
         loop {
             match local_file.read(&mut buffer) {
                 Ok(0) => break, // EOF
                 Ok(bytes_read) => {
-                    remote_file.write_all(&buffer[..bytes_read])
-                        .with_context(|| "Failed to write to remote file")?;
+                    throttle.consume(bytes_read);
+                    let written = remote_file.write_all(&buffer[..bytes_read])
+                        .with_context(|| "Failed to write to remote file");
+                    if let Err(e) = written {
+                        progress.finish_with_error(&e.to_string());
+                        if let Some(journal) = &journal {
+                            journal.flush()?;
+                        }
+                        return Err(e);
+                    }
+                    if let Some(cursor) = cursor.as_mut() {
+                        cursor.advance(&buffer[..bytes_read])?;
+                    }
                     total_transferred += bytes_read as u64;
                     progress.update(total_transferred);
                 }
@@ -421,30 +702,505 @@ impl Uploader {
         }
 
         // 确保数据写入完成
-        remote_file.fsync().ok(); // 忽略fsync错误，某些服务器可能不支持
+        remote_file.finish()?;
+        remote_file.get_mut().fsync().ok(); // 忽略fsync错误，某些服务器可能不支持
+        if let Some(journal) = &journal {
+            journal.flush()?;
+            journal.finish()?;
+        }
 
         progress.finish();
         println!("✅ Upload completed: {}", remote_path);
         Ok(())
     }
 
-    fn upload_file_worker(sftp: &Sftp, task: &UploadTask, config: &Config) -> Result<()> {
-        let mut local_file = File::open(&task.local_path)?;
+    // 单个文件上传完成后校验远程临时文件，通过后重命名为目标文件并记录统计
+    fn finish_file(&self, sftp: &Sftp, local_path: &Path, remote_path: &str, file_size: u64) -> Result<()> {
+        let result = verify_after_transfer(&self.config, &self.session.client, sftp, local_path, &remote_part_path(remote_path), &self.summary)
+            .and_then(|_| Self::commit_remote_part(sftp, remote_path))
+            .and_then(|_| self.preserve_attributes(sftp, local_path, remote_path));
+        if let Err(e) = result {
+            self.summary.record_failure(&local_path.display().to_string(), &e);
+            return Err(e);
+        }
+        self.summary.record_transferred(file_size);
+        Ok(())
+    }
+
+    // 同步模式下按大小、修改时间或摘要判断远程文件是否需要更新
+    fn sync_needs_copy(&self, options: sync::SyncOptions, sftp: &Sftp, local_path: &Path, remote_path: &str, size: u64, mtime: u64) -> Result<bool> {
+        let existing = sftp.stat(Path::new(remote_path))
+            .ok()
+            .filter(|stat| stat.is_file())
+            .map(|stat| FileFacts { size: stat.size.unwrap_or(0), mtime: stat.mtime.unwrap_or(0) });
+        let algorithm = self.config.hash_algorithm;
+        options.needs_copy(FileFacts { size, mtime }, existing, || {
+            let local = calculate_hash(local_path, algorithm)?;
+            let remote = remote_hash(&self.session.client, sftp, remote_path, algorithm)?;
+            Ok(local != remote)
+        })
+    }
+
+    // 同步时删除远程目录中本地已不存在的条目
+    // 被排除或被忽略的远程条目不会删除
+    fn delete_extraneous(&self, sftp: &Sftp, entries: &[(PathBuf, Metadata, bool)], local_dir: &Path, remote_dir: &str, relative: &str, ignores: &IgnoreStack) -> Result<()> {
+        let source: HashMap<String, bool> = entries
+            .iter()
+            .filter_map(|(path, metadata, _)| Some((path.file_name()?.to_string_lossy().to_string(), metadata.is_dir())))
+            .collect();
+        let remote_entries = sftp.readdir(Path::new(remote_dir))?
+            .into_iter()
+            .filter_map(|(path, stat)| Some((path.file_name()?.to_string_lossy().to_string(), stat.is_dir())))
+            .filter(|(name, is_dir)| self.config.filter.allows(&join_relative(relative, name), *is_dir))
+            .filter(|(name, is_dir)| !ignores.is_ignored(&local_dir.join(name), *is_dir));
+
+        for (name, is_dir) in sync::extraneous(&source, remote_entries) {
+            let path = format!("{}/{}", remote_dir.trim_end_matches('/'), name);
+            if self.plan.is_enabled() {
+                self.plan.delete(&path);
+                continue;
+            }
+            println!("Deleting remote {}", path);
+            match sync::remove_remote(sftp, &path, is_dir) {
+                Ok(()) => self.summary.record_deleted(),
+                Err(e) => self.summary.record_failure(&path, &e),
+            }
+        }
+        Ok(())
+    }
+
+    // 远程目标是已存在的普通文件时增量上传到远程临时文件
+    // 远程无法运行辅助脚本时清理临时文件并返回 None，由调用方回退到完整上传
+    // 增量数据与普通上传一样受限速约束，progress 为 None 时由调用方在完成后计入进度
+    fn try_delta_upload(client: &SshClient, sftp: &Sftp, local_path: &Path, remote_path: &str, throttle: Throttle, progress: Option<&ProgressTracker>) -> Option<DeltaStats> {
+        let remote_size = match sftp.stat(Path::new(remote_path)) {
+            Ok(stat) if stat.is_file() => stat.size.unwrap_or(0),
+            _ => return None,
+        };
+        let part = remote_part_path(remote_path);
+        let result = delta::upload_delta(client, local_path, remote_path, &part, remote_size, throttle, |bytes| {
+            if let Some(progress) = progress {
+                progress.add_bytes(bytes);
+            }
+        });
+        match result {
+            Ok(stats) => {
+                log::info!("Delta upload of {}: {} bytes transferred, {} bytes reused",
+                           local_path.display(), stats.literal_bytes, stats.matched_bytes);
+                Some(stats)
+            }
+            Err(e) => {
+                println!("Delta transfer not possible for {}: {}; falling back to a full upload", local_path.display(), e);
+                let _ = sftp.unlink(Path::new(&part));
+                None
+            }
+        }
+    }
+
+    // 按配置把本地文件或目录的属性应用到远程
+    fn preserve_attributes(&self, sftp: &Sftp, local_path: &Path, remote_path: &str) -> Result<()> {
+        let which = self.config.preserved_attributes();
+        if which.is_empty() {
+            return Ok(());
+        }
+        let metadata = std::fs::metadata(local_path)?;
+        FileAttributes::from_local(&metadata).apply_remote(sftp, remote_path, which)
+    }
+
+    // 顺序上传时打开断点记录，返回记录和续传偏移量；remote_size 为远程临时文件的当前大小
+    // 不超过一个分块的小文件不记录断点，总是从头上传
+    fn open_sequential_journal(
+        config: &Config,
+        remote_path: &str,
+        file_size: u64,
+        mtime: u64,
+        resume: bool,
+        remote_size: Option<u64>,
+    ) -> Result<(Option<ResumeJournal>, u64)> {
+        let chunk_size = config.chunk_size as u64;
+        if file_size <= chunk_size {
+            return Ok((None, 0));
+        }
+
+        let path = Self::journal_path(config, remote_path)?;
+        let persisted = path.is_some();
+        let (journal, _) = ResumeJournal::open(
+            path,
+            remote_path,
+            file_size,
+            mtime,
+            chunk_size,
+            config.hash_algorithm,
+            resume && remote_size.is_some(),
+        )?;
+        let offset = if persisted {
+            journal.completed_prefix()
+        } else if resume {
+            // 没有落盘的断点记录时（未启用 --resume 的失败重试），按远程临时文件的大小续传
+            // SFTP 写入都经过服务器确认，取整到分块边界以便继续记录分块校验和
+            remote_size.unwrap_or(0).min(file_size) / chunk_size * chunk_size
+        } else {
+            0
+        };
+        Ok((Some(journal), offset))
+    }
+
+    // 只有启用 --resume 时才把上传的断点记录写入用户状态目录，否则只保存在内存中
+    fn journal_path(config: &Config, remote_path: &str) -> Result<Option<PathBuf>> {
+        if !config.resume {
+            return Ok(None);
+        }
+        ResumeInfo::upload_journal_path(&config.username, &config.host, config.port, remote_path).map(Some)
+    }
+
+    // 把远程临时文件重命名为目标文件，服务器不支持覆盖时先删除旧文件
+    fn commit_remote_part(sftp: &Sftp, remote_path: &str) -> Result<()> {
+        let part = remote_part_path(remote_path);
+        let flags = ssh2::RenameFlags::OVERWRITE | ssh2::RenameFlags::ATOMIC | ssh2::RenameFlags::NATIVE;
+        if sftp.rename(Path::new(&part), Path::new(remote_path), Some(flags)).is_ok() {
+            return Ok(());
+        }
+        sftp.unlink(Path::new(remote_path)).ok();
+        sftp.rename(Path::new(&part), Path::new(remote_path), None)
+            .with_context(|| format!("Failed to rename {} to {}", part, remote_path))?;
+        Ok(())
+    }
+
+    // 打开远程文件并定位到续传位置，从头上传时截断远程文件
+    fn open_remote_at(sftp: &Sftp, remote_path: &str, offset: u64) -> Result<ssh2::File> {
+        if offset == 0 {
+            return Ok(sftp.create(Path::new(remote_path))?);
+        }
+        let mut remote_file = sftp.open_mode(
+            Path::new(remote_path),
+            ssh2::OpenFlags::WRITE,
+            0o644,
+            OpenType::File
+        )?;
+        remote_file.seek(SeekFrom::Start(offset))?;
+        Ok(remote_file)
+    }
+
+    // 根据状态目录中的断点记录计算续传偏移量，没有匹配的记录时从头上传
+    fn resume_offset(&self, remote_path: &str, size: u64, mtime: u64) -> u64 {
+        let chunk_size = self.config.chunk_size as u64;
+        Self::journal_path(&self.config, remote_path)
+            .ok()
+            .flatten()
+            .and_then(|path| ResumeInfo::load_matching(&path, remote_path, size, mtime, chunk_size))
+            .map(|info| info.completed_prefix())
+            .unwrap_or(0)
+    }
+
+    // 计算目录上传时文件的续传偏移量，返回 None 表示文件已完整上传
+    fn planned_offset(&self, sftp: &Sftp, remote_path: &str, size: u64, mtime: u64) -> Option<u64> {
+        if !self.config.resume {
+            return Some(0);
+        }
+        // 存在未完成的远程临时文件时，根据断点记录续传
+        if sftp.stat(Path::new(&remote_part_path(remote_path))).is_ok() {
+            return Some(self.resume_offset(remote_path, size, mtime));
+        }
+        // 同步模式由大小和修改时间决定是否需要上传
+        if self.config.sync_options().is_some() {
+            return Some(0);
+        }
+        // 没有临时文件且目标文件大小一致，说明上次已完整上传
+        let remote_size = sftp.stat(Path::new(remote_path)).ok().and_then(|stat| stat.size);
+        if remote_size == Some(size) {
+            None
+        } else {
+            Some(0)
+        }
+    }
+
+    // 将大文件划分为多个分块，由多个独立的SSH会话并行写入远程文件的不同位置
+    fn upload_file_chunked(&self, sftp: &Sftp, local_path: &Path, remote_path: &str, file_size: u64, mtime: u64, resume: bool) -> Result<()> {
+        let chunk_size = self.config.chunk_size as u64;
+        println!("Uploading file in chunks: {} -> {} ({} bytes, {} byte chunks)",
+                 local_path.display(), remote_path, file_size, chunk_size);
+
+        // 断点记录保存在用户状态目录中，源文件变化时重新开始
+        let (journal, resuming) = ResumeJournal::open(
+            Self::journal_path(&self.config, remote_path)?,
+            remote_path,
+            file_size,
+            mtime,
+            chunk_size,
+            self.config.hash_algorithm,
+            resume && sftp.stat(Path::new(&remote_part_path(remote_path))).is_ok(),
+        )?;
+        let journal = Arc::new(journal);
+
+        let progress = Arc::new(ProgressTracker::new(file_size, &format!("Uploading {}", local_path.display())));
+        if resuming {
+            let offset = journal.transferred_size();
+            println!("Resuming chunked upload: {} of {} bytes already uploaded", offset, file_size);
+            progress.update(offset);
+        }
+
+        // 确保远程目录存在，全新上传时先截断远程临时文件
+        if let Some(parent) = Path::new(remote_path).parent() {
+            if !parent.as_os_str().is_empty() {
+                self.ensure_remote_directory(sftp, parent)?;
+            }
+        }
+        let remote_part = remote_part_path(remote_path);
+        if !resuming {
+            sftp.create(Path::new(&remote_part))?;
+        }
+
+        let pending = journal.pending_chunks();
+        let (tx, rx): (Sender<ChunkTask>, Receiver<ChunkTask>) = bounded(100);
+        // 同一文件的所有分块共用单文件限速
+        let throttle = self.limiter.for_file();
+
+        // 每个工作线程使用独立的SSH会话
+        let mut handles = Vec::new();
+        for _ in 0..self.config.threads.min(pending.len()) {
+            let rx = rx.clone();
+            let session = self.session.clone_session()?;
+            let local_path = local_path.to_path_buf();
+            let remote_part = remote_part.clone();
+            let journal = Arc::clone(&journal);
+            let progress = Arc::clone(&progress);
+            let throttle = throttle.clone();
+            let retry = self.config.retry;
+            let sparse = self.config.sparse;
+
+            let handle = thread::spawn(move || {
+                let mut local_file = match File::open(&local_path).and_then(|local_file| SparseReader::new(local_file, sparse)) {
+                    Ok(local_file) => local_file,
+                    Err(e) => {
+                        // 剩余分块交由其他线程处理
+                        eprintln!("Failed to open {} for chunked upload: {}", local_path.display(), e);
+                        return;
+                    }
+                };
+                // SFTP 通道和远程文件在第一个分块时打开，打开失败也按重试和重连处理
+                let mut sftp = None;
+                let mut remote_file = None;
+                while let Ok(task) = rx.recv() {
+                    // 续传时未完成的分块可能残留旧数据，只有全新上传才跳过全零块
+                    let what = format!("Upload of chunk {} of {}", task.chunk_id, local_path.display());
+                    // 失败后重新打开远程文件，重新连接后旧的文件句柄已经失效
+                    let result = session.run_reconnecting(&retry, &what, &mut sftp, |sftp, failed| {
+                        if failed {
+                            remote_file = None;
+                        }
+                        let remote_file = match &mut remote_file {
+                            Some(file) => file,
+                            None => remote_file.insert(sftp.open_mode(Path::new(&remote_part), ssh2::OpenFlags::WRITE, 0o644, OpenType::File)?),
+                        };
+                        Self::upload_chunk_worker(&mut local_file, remote_file, &task, &progress, &throttle, journal.hash_algorithm(), sparse && !resuming)
+                    })
+                    .and_then(|checksum| journal.complete_chunk(task.chunk_id, Some(checksum)));
+                    if let Err(e) = result {
+                        eprintln!("Upload error for chunk {} of {}: {}", task.chunk_id, local_path.display(), e);
+                    }
+                }
+                if let Some(remote_file) = remote_file.as_mut() {
+                    remote_file.fsync().ok();
+                }
+            });
+            handles.push(handle);
+        }
+        drop(rx);
+
+        for (chunk_id, start, end) in pending {
+            // 所有工作线程都已退出时停止分发
+            if tx.send(ChunkTask { chunk_id, start, end }).is_err() {
+                break;
+            }
+        }
+        drop(tx);
+
+        for handle in handles {
+            handle.join().map_err(|_| TransferError::ThreadJoinError)?;
+        }
+
+        journal.flush()?;
+
+        let failed = journal.pending_chunks().len();
+        if failed > 0 {
+            progress.finish_with_error("some chunks failed");
+            return Err(anyhow::anyhow!(
+                "{} chunk(s) of {} failed to upload; rerun with --resume to send the missing chunks",
+                failed, local_path.display()
+            ));
+        }
+
+        journal.finish()?;
+        progress.finish();
+        println!("✅ Upload completed: {}", remote_path);
+        Ok(())
+    }
+
+    fn upload_chunk_worker(
+        local_file: &mut SparseReader,
+        remote_file: &mut ssh2::File,
+        task: &ChunkTask,
+        progress: &ProgressTracker,
+        throttle: &Throttle,
+        hash_algorithm: HashAlgorithm,
+        sparse: bool,
+    ) -> Result<String> {
+        local_file.seek(SeekFrom::Start(task.start))?;
+        remote_file.seek(SeekFrom::Start(task.start))?;
+        let mut remote_file = SparseWriter::new(remote_file, sparse)?;
+        let mut hasher = hash_algorithm.hasher();
+        // 失败时撤回本次计入的进度，重试的分块不会重复计数
+        let mut counted = progress.attempt();
+
+        let mut buffer = vec![0u8; (task.end - task.start + 1).min(8 * 1024 * 1024) as usize];
+        let mut offset = task.start;
+        while offset <= task.end {
+            let want = ((task.end - offset + 1) as usize).min(buffer.len());
+            let bytes_read = local_file.read(&mut buffer[..want])?;
+            if bytes_read == 0 {
+                return Err(anyhow::anyhow!("Unexpected end of local file at offset {}", offset));
+            }
+            throttle.consume(bytes_read);
+            remote_file.write_all(&buffer[..bytes_read])
+                .with_context(|| "Failed to write to remote file")?;
+            hasher.update(&buffer[..bytes_read]);
+            offset += bytes_read as u64;
+            counted.add_bytes(bytes_read as u64);
+        }
+        remote_file.finish()?;
+        counted.commit();
+        // 返回分块校验和，写入断点记录
+        Ok(hasher.finalize())
+    }
+
+    // 归档模式：当前目录需要上传的文件打包为一个 tar 流，远程解包为临时文件后逐个校验并提交
+    fn run_upload_archive(&self, sftp: &Sftp, local_dir: &Path, remote_dir: &str, tasks: Vec<UploadTask>, total_size: u64) {
+        if tasks.is_empty() {
+            return;
+        }
+        let progress = ProgressTracker::new(total_size, &format!("Uploading from {}", local_dir.display()));
+        let attributes = self.config.preserved_attributes();
+        let files = tasks.iter().map(|task| {
+            let name = Path::new(&task.remote_path).file_name().unwrap_or_default();
+            (task.local_path.as_path(), part_path(name).to_string_lossy().to_string())
+        });
+        let result = archive::send_archive(&self.session.client, remote_dir, files, self.config.stream_compression,
+                                            progress.wire_bytes(), self.limiter.shared(), |index| {
+            progress.set_current_file(&tasks[index].local_path.display().to_string());
+            progress.add_bytes(tasks[index].effective_size);
+        });
+        if let Err(e) = result {
+            eprintln!("Archive upload to {} failed: {}", remote_dir, e);
+        }
+
+        // 解包中断时临时文件可能不完整，长度不一致的不提交
+        for task in tasks {
+            let remote_part = remote_part_path(&task.remote_path);
+            let result = match sftp.stat(Path::new(&remote_part)) {
+                Ok(stat) if stat.size == Some(task.size) => Ok(()),
+                Ok(_) => Err(anyhow::anyhow!("Incomplete file in archive upload: {}", remote_part)),
+                Err(_) => Err(anyhow::anyhow!("File was not extracted on the remote host: {}", remote_part)),
+            };
+            let result = result
+                .and_then(|_| verify_after_transfer(&self.config, &self.session.client, sftp, &task.local_path, &remote_part, &self.summary))
+                .and_then(|_| Self::commit_remote_part(sftp, &task.remote_path))
+                .and_then(|_| task.attributes.apply_remote(sftp, &task.remote_path, attributes));
+            match result {
+                Ok(()) => self.summary.record_transferred(task.size),
+                Err(e) => {
+                    let e = classify(e, &task.local_path, &task.remote_path);
+                    eprintln!("Upload error for {}: {}", task.local_path.display(), e);
+                    self.summary.record_failure(&task.local_path.display().to_string(), &e);
+                }
+            }
+        }
+        progress.finish();
+    }
+
+    // 用多个工作线程上传同一目录中的文件
+    fn run_upload_tasks(&self, local_dir: &Path, tasks: Vec<UploadTask>, total_size: u64) -> Result<()> {
+        // 为当前目录创建单个总进度条
+        let progress = Arc::new(ProgressTracker::new(
+            total_size, 
+            &format!("Uploading from {}", local_dir.display())
+        ));
+
+        // 创建上传任务
+        let (tx, rx): (Sender<UploadTask>, Receiver<UploadTask>) = bounded(100);
+
+        // 启动工作线程
+        let mut handles = Vec::new();
+        for _ in 0..self.config.threads {
+            let rx = rx.clone();
+            let session = self.session.clone_session()?;
+            let config = Arc::clone(&self.config);
+            let progress = Arc::clone(&progress);
+            let summary = Arc::clone(&self.summary);
+            let limiter = Arc::clone(&self.limiter);
+            let attributes = config.preserved_attributes();
+
+            let handle = thread::spawn(move || {
+                let mut sftp = None;
+                while let Ok(task) = rx.recv() {
+                    let what = format!("Upload of {}", task.local_path.display());
+                    let result = session.run_reconnecting(&config.retry, &what, &mut sftp, |sftp, failed| {
+                        Self::upload_file_worker(&session, sftp, &task, &config, &limiter, config.resume || failed)
+                            .and_then(|_| verify_after_transfer(&config, &session, sftp, &task.local_path, &remote_part_path(&task.remote_path), &summary))
+                            .and_then(|_| Self::commit_remote_part(sftp, &task.remote_path))
+                            .and_then(|_| task.attributes.apply_remote(sftp, &task.remote_path, attributes))
+                    });
+                    if let Err(e) = result {
+                        let e = classify(e, &task.local_path, &task.remote_path);
+                        eprintln!("Upload error for {}: {}", task.local_path.display(), e);
+                        summary.record_failure(&task.local_path.display().to_string(), &e);
+                    } else {
+                        progress.add_bytes(task.effective_size);
+                        summary.record_transferred(task.size);
+                    }
+                }
+            });
+            handles.push(handle);
+        }
+
+        // 发送上传任务
+        for task in tasks {
+            tx.send(task)?;
+        }
+        drop(tx);
+
+        // 等待所有工作线程完成
+        for handle in handles {
+            handle.join().map_err(|_| TransferError::ThreadJoinError)?;
+        }
+
+        progress.finish();
+        Ok(())
+    }
+
+    fn upload_file_worker(client: &SshClient, sftp: &Sftp, task: &UploadTask, config: &Config, limiter: &RateLimiter, resume: bool) -> Result<()> {
+        // 远程已有旧版本时先尝试增量上传
+        if config.delta && task.effective_size == task.size
+            && Self::try_delta_upload(client, sftp, &task.local_path, &task.remote_path, limiter.for_file(), None).is_some()
+        {
+            return Ok(());
+        }
+
+        // 根据断点记录确定远程临时文件的续传位置
+        let remote_part = remote_part_path(&task.remote_path);
+        let remote_size = sftp.stat(Path::new(&remote_part)).ok().and_then(|stat| stat.size);
+        let (journal, offset) = Self::open_sequential_journal(
+            config, &task.remote_path, task.size, task.mtime, resume, remote_size,
+        )?;
+
+        let mut local_file = SparseReader::new(File::open(&task.local_path)?, config.sparse)?;
         
         // 设置偏移量
-        if task.offset > 0 {
-            local_file.seek(SeekFrom::Start(task.offset))?;
+        if offset > 0 {
+            local_file.seek(SeekFrom::Start(offset))?;
         }
         
         // 创建或打开远程文件
-        let mut remote_file = if task.offset > 0 {
-            sftp.open_mode(
-                Path::new(&task.remote_path),
-                ssh2::OpenFlags::WRITE | ssh2::OpenFlags::APPEND,
-                0o644,
-                OpenType::File
-            )?
-        } else {
+        if offset == 0 {
             // 确保父目录存在
             if let Some(parent) = Path::new(&task.remote_path).parent() {
                 if !parent.as_os_str().is_empty() {
@@ -457,8 +1213,9 @@ impl Uploader {
                     }
                 }
             }
-            sftp.create(Path::new(&task.remote_path))?
-        };
+        }
+        let remote_file = Self::open_remote_at(sftp, &remote_part, offset)?;
+        let mut remote_file = SparseWriter::new(remote_file, config.sparse && offset == 0)?;
     
         // 对于大文件使用更大的缓冲区
         let buffer_size = if task.effective_size > 10 * 1024 * 1024 {
@@ -469,6 +1226,8 @@ impl Uploader {
         };
         
         let mut buffer = vec![0u8; buffer_size];
+        let mut cursor = journal.as_ref().map(|j| ChunkCursor::new(j, config.chunk_size as u64, offset));
+        let throttle = limiter.for_file();
         
         // 添加进度反馈
         let mut bytes_uploaded = 0;
@@ -484,7 +1243,17 @@ impl Uploader {
             match local_file.read(&mut buffer) {
                 Ok(0) => break, // EOF
                 Ok(bytes_read) => {
-                    remote_file.write_all(&buffer[..bytes_read])?;
+                    throttle.consume(bytes_read);
+                    let written = remote_file.write_all(&buffer[..bytes_read]);
+                    if let Err(e) = written {
+                        if let Some(journal) = &journal {
+                            journal.flush()?;
+                        }
+                        return Err(e.into());
+                    }
+                    if let Some(cursor) = cursor.as_mut() {
+                        cursor.advance(&buffer[..bytes_read])?;
+                    }
                     bytes_uploaded += bytes_read as u64;
                     
                     // 减少进度更新频率
@@ -498,15 +1267,34 @@ impl Uploader {
         }
     
         // 确保数据写入完成
-        remote_file.fsync().ok();
+        remote_file.finish()?;
+        remote_file.get_mut().fsync().ok();
+        if let Some(journal) = &journal {
+            journal.flush()?;
+            journal.finish()?;
+        }
         Ok(())
     }
 }
 
+// 远程目标文件对应的隐藏临时文件
+fn remote_part_path(remote_path: &str) -> String {
+    part_path(remote_path).to_string_lossy().to_string()
+}
+
 #[derive(Debug)]
 struct UploadTask {
     local_path: PathBuf,
     remote_path: String,
-    offset: u64,         // 断点续传的起始位置
+    size: u64,           // 本地文件大小
+    mtime: u64,          // 本地文件修改时间
     effective_size: u64,  // 实际需要上传的大小
-}
\ No newline at end of file
+    attributes: FileAttributes,  // 需要保留的本地文件属性
+}
+
+#[derive(Debug)]
+struct ChunkTask {
+    chunk_id: usize,
+    start: u64,
+    end: u64,           // 包含结束位置
+}
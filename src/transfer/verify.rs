@@ -0,0 +1,77 @@
+// 传输完成后的完整性校验
+use crate::config::Config;
+use crate::ssh::client::shell_quote;
+use crate::ssh::SshClient;
+use crate::transfer::summary::TransferSummary;
+use crate::utils::error::TransferError;
+use crate::utils::file::{calculate_hash, hash_reader, HashAlgorithm};
+use anyhow::Result;
+use ssh2::Sftp;
+use std::io::BufReader;
+use std::path::Path;
+
+// 启用 --verify 时校验刚传输完成的文件，并记录到汇总
+pub fn verify_after_transfer(
+    config: &Config,
+    client: &SshClient,
+    sftp: &Sftp,
+    local_path: &Path,
+    remote_path: &str,
+    summary: &TransferSummary,
+) -> Result<()> {
+    if !config.verify {
+        return Ok(());
+    }
+    let algorithm = config.hash_algorithm;
+    let digest = verify_transfer(client, sftp, local_path, remote_path, algorithm)?;
+    log::info!("Verified {} ({} {})", remote_path, algorithm.name(), digest);
+    summary.record_verified();
+    Ok(())
+}
+
+// 比较本地文件和远程文件的摘要，不一致时返回 VerificationFailed
+pub fn verify_transfer(
+    client: &SshClient,
+    sftp: &Sftp,
+    local_path: &Path,
+    remote_path: &str,
+    algorithm: HashAlgorithm,
+) -> Result<String> {
+    let local = calculate_hash(local_path, algorithm)?;
+    let remote = remote_hash(client, sftp, remote_path, algorithm)?;
+
+    if local != remote {
+        return Err(TransferError::VerificationFailed {
+            path: remote_path.to_string(),
+            local,
+            remote,
+        }.into());
+    }
+    Ok(local)
+}
+
+// 优先在远程执行对应的摘要命令，远程没有该工具时通过SFTP读回文件计算
+pub fn remote_hash(client: &SshClient, sftp: &Sftp, remote_path: &str, algorithm: HashAlgorithm) -> Result<String> {
+    let command = format!("{} {} 2>/dev/null", algorithm.remote_command(), shell_quote(remote_path));
+    if let Ok(output) = client.exec(&command) {
+        if let Some(digest) = parse_digest(&output, algorithm.hex_len()) {
+            return Ok(digest);
+        }
+    }
+
+    log::debug!("{} unavailable on remote, streaming {} back over SFTP", algorithm.remote_command(), remote_path);
+    let remote_file = sftp.open(Path::new(remote_path))?;
+    hash_reader(BufReader::new(remote_file), algorithm)
+}
+
+// 从 "<摘要>  <文件名>" 格式的输出中取出摘要
+fn parse_digest(output: &str, hex_len: usize) -> Option<String> {
+    let digest = output.split_whitespace().next()?;
+    // xxhsum 会在摘要前加上算法前缀
+    let digest = digest.strip_prefix("XXH3_").unwrap_or(digest);
+    if digest.len() == hex_len && digest.chars().all(|c| c.is_ascii_hexdigit()) {
+        Some(digest.to_ascii_lowercase())
+    } else {
+        None
+    }
+}
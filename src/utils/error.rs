@@ -1,4 +1,7 @@
 // 错误处理
+use std::io;
+use std::path::Path;
+use std::time::Duration;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -18,12 +21,306 @@ pub enum TransferError {
     #[error("Permission denied: {path}")]
     PermissionDenied { path: String },
     
+    #[error("Integrity check failed for {path}: local {local}, remote {remote}")]
+    VerificationFailed { path: String, local: String, remote: String },
+    
+    #[error("Not enough free space on {path}: {needed} bytes needed, {available} bytes available")]
+    InsufficientSpace { path: String, needed: u64, available: u64 },
+    
     #[error("Network error: {message}")]
     NetworkError { message: String },
     
+    #[error("Host key verification failed for {host}: the server key {fingerprint} does not match the one recorded in {known_hosts}")]
+    HostKeyMismatch { host: String, fingerprint: String, known_hosts: String },
+    
+    #[error("Host key for {host} is not in {known_hosts} (server key {fingerprint}); add it to the file or use --host-key-policy accept-new")]
+    HostKeyUnknown { host: String, fingerprint: String, known_hosts: String },
+    
+    #[error("{operation} timed out after {seconds}s")]
+    Timeout { operation: String, seconds: u64 },
+    
+    #[error("Connection to {address} lost, gave up after {attempts} reconnect attempts")]
+    ConnectionLost { address: String, attempts: u32 },
+    
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
     
     #[error("SSH error: {0}")]
     SshError(#[from] ssh2::Error),
-}
\ No newline at end of file
+}
+
+impl TransferError {
+    // 重试可能成功的错误：网络中断、超时和 SFTP 服务端的临时失败
+    // 文件不存在、权限不足、空间不足等重试也不会成功，应立即失败
+    pub fn is_transient(&self) -> bool {
+        match self {
+            TransferError::NetworkError { .. } | TransferError::Timeout { .. } => true,
+            TransferError::IoError(e) => io_error_is_transient(e),
+            TransferError::SshError(e) => ssh_error_is_transient(e),
+            TransferError::AuthenticationFailed
+            | TransferError::DirectoryNotAllowed
+            | TransferError::ThreadJoinError
+            | TransferError::FileNotFound { .. }
+            | TransferError::PermissionDenied { .. }
+            | TransferError::VerificationFailed { .. }
+            | TransferError::InsufficientSpace { .. }
+            | TransferError::HostKeyMismatch { .. }
+            | TransferError::HostKeyUnknown { .. }
+            | TransferError::ConnectionLost { .. } => false,
+        }
+    }
+}
+
+// 沿错误链找到第一个能判断的错误；无法判断的按永久错误处理
+pub fn is_transient(error: &anyhow::Error) -> bool {
+    for cause in error.chain() {
+        if let Some(e) = cause.downcast_ref::<TransferError>() {
+            return e.is_transient();
+        }
+        if let Some(e) = cause.downcast_ref::<ssh2::Error>() {
+            return ssh_error_is_transient(e);
+        }
+        if let Some(e) = cause.downcast_ref::<io::Error>() {
+            return io_error_is_transient(e);
+        }
+    }
+    false
+}
+
+// 错误明确表示 SSH 连接已断开：套接字收发失败、对端断开或 SFTP 连接丢失
+// SFTP 读写错误经 io::Error 转换后丢失了错误码，无法从错误本身判断，需要探测连接
+pub fn is_disconnect(error: &anyhow::Error) -> bool {
+    for cause in error.chain() {
+        // 超时后会话可能停在半个数据包上，不能继续使用
+        if let Some(TransferError::Timeout { .. }) = cause.downcast_ref::<TransferError>() {
+            return true;
+        }
+        if let Some(e) = cause.downcast_ref::<ssh2::Error>() {
+            return match e.code() {
+                // LIBSSH2_ERROR_SOCKET_SEND、SOCKET_DISCONNECT、SOCKET_RECV、BAD_SOCKET
+                ssh2::ErrorCode::Session(code) => matches!(code, -7 | -13 | -43 | -45),
+                // LIBSSH2_FX_NO_CONNECTION、CONNECTION_LOST
+                ssh2::ErrorCode::SFTP(code) => matches!(code, 6 | 7),
+            };
+        }
+        if let Some(e) = cause.downcast_ref::<io::Error>() {
+            return matches!(
+                e.kind(),
+                io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+                    | io::ErrorKind::NotConnected
+                    | io::ErrorKind::BrokenPipe
+            );
+        }
+    }
+    false
+}
+
+// 沿错误链查找 libssh2 的 TIMEOUT、SOCKET_TIMEOUT 或套接字超时
+fn is_timeout(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        if let Some(e) = cause.downcast_ref::<ssh2::Error>() {
+            return matches!(e.code(), ssh2::ErrorCode::Session(-9 | -30));
+        }
+        if let Some(e) = cause.downcast_ref::<io::Error>() {
+            return e.kind() == io::ErrorKind::TimedOut;
+        }
+        false
+    })
+}
+
+// 超时错误转换为 TransferError::Timeout，operation 描述超时的操作；其他错误原样返回
+pub fn timeout_error(error: anyhow::Error, operation: &str, timeout: Option<Duration>) -> anyhow::Error {
+    match timeout {
+        Some(timeout) if is_timeout(&error) => TransferError::Timeout {
+            operation: operation.to_string(),
+            seconds: timeout.as_secs(),
+        }.into(),
+        _ => error,
+    }
+}
+
+// 把底层的文件不存在、权限不足和网络错误转换为对应的 TransferError
+// 来自 SFTP 的错误报告远程路径，带系统错误码的本地错误报告本地路径
+// 原始错误保留在错误链中；已经是 TransferError 的错误原样返回
+pub fn classify(error: anyhow::Error, local_path: &Path, remote_path: &str) -> anyhow::Error {
+    if error.chain().any(|cause| cause.is::<TransferError>()) {
+        return error;
+    }
+    let (category, remote) = category(&error);
+    let path = if remote { remote_path.to_string() } else { local_path.display().to_string() };
+    let classified = match category {
+        Category::NotFound => TransferError::FileNotFound { path },
+        Category::PermissionDenied => TransferError::PermissionDenied { path },
+        Category::Network => TransferError::NetworkError { message: error.root_cause().to_string() },
+        Category::Other => return error,
+    };
+    error.context(classified)
+}
+
+enum Category {
+    NotFound,
+    PermissionDenied,
+    Network,
+    Other,
+}
+
+// 沿错误链找到第一个 ssh2 或 io 错误并归类，同时返回错误是否来自远程
+fn category(error: &anyhow::Error) -> (Category, bool) {
+    for cause in error.chain() {
+        if let Some(e) = cause.downcast_ref::<ssh2::Error>() {
+            let category = match e.code() {
+                ssh2::ErrorCode::SFTP(code) => sftp_category(code),
+                ssh2::ErrorCode::Session(-7 | -13 | -43 | -45) => Category::Network,
+                ssh2::ErrorCode::Session(_) => Category::Other,
+            };
+            return (category, true);
+        }
+        if let Some(e) = cause.downcast_ref::<io::Error>() {
+            let category = match e.kind() {
+                io::ErrorKind::NotFound => Category::NotFound,
+                io::ErrorKind::PermissionDenied => Category::PermissionDenied,
+                io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionAborted
+                | io::ErrorKind::ConnectionRefused
+                | io::ErrorKind::NotConnected
+                | io::ErrorKind::BrokenPipe => Category::Network,
+                _ => match sftp_status(e) {
+                    Some(code) => sftp_category(code),
+                    None => Category::Other,
+                },
+            };
+            // ssh2 转换来的 io::Error 没有系统错误码
+            return (category, e.raw_os_error().is_none());
+        }
+    }
+    (Category::Other, false)
+}
+
+fn sftp_category(code: i32) -> Category {
+    match code {
+        // LIBSSH2_FX_NO_SUCH_FILE、NO_SUCH_PATH
+        2 | 10 => Category::NotFound,
+        // LIBSSH2_FX_PERMISSION_DENIED、WRITE_PROTECT
+        3 | 12 => Category::PermissionDenied,
+        // LIBSSH2_FX_NO_CONNECTION、CONNECTION_LOST
+        6 | 7 => Category::Network,
+        _ => Category::Other,
+    }
+}
+
+fn ssh_error_is_transient(error: &ssh2::Error) -> bool {
+    match error.code() {
+        // LIBSSH2_ERROR_*：握手失败、套接字收发失败、断开、超时、通道关闭
+        ssh2::ErrorCode::Session(code) => matches!(code, -2 | -3 | -5 | -7 | -8 | -9 | -13 | -21 | -26 | -30 | -37 | -43 | -45),
+        ssh2::ErrorCode::SFTP(code) => sftp_code_is_transient(code),
+    }
+}
+
+// LIBSSH2_FX_*：BAD_MESSAGE、NO_CONNECTION、CONNECTION_LOST、LOCK_CONFLICT
+// FX_FAILURE 是服务端不区分原因的通用失败，多数是磁盘满、配额等永久错误，不重试
+fn sftp_code_is_transient(code: i32) -> bool {
+    matches!(code, 5 | 6 | 7 | 17)
+}
+
+fn io_error_is_transient(error: &io::Error) -> bool {
+    // ssh2 把 SFTP 读写错误转换为不带系统错误码的 Other：SFTP 状态错误按状态码判断，
+    // 其余的来自 SSH 会话本身（通道或套接字收发失败），按网络错误处理
+    if error.raw_os_error().is_none() && error.kind() == io::ErrorKind::Other {
+        return sftp_status(error).is_none_or(sftp_code_is_transient);
+    }
+    matches!(
+        error.kind(),
+        io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::NotConnected
+            | io::ErrorKind::BrokenPipe
+            | io::ErrorKind::TimedOut
+            | io::ErrorKind::WouldBlock
+            | io::ErrorKind::Interrupted
+            | io::ErrorKind::UnexpectedEof
+    )
+}
+
+// ssh2 转换为 io::Error 时只保留了错误信息，SFTP 状态码对应的信息是固定的，据此还原状态码
+fn sftp_status(error: &io::Error) -> Option<i32> {
+    const MESSAGES: [&str; 21] = [
+        "end of file",
+        "no such file",
+        "permission denied",
+        "failure",
+        "bad message",
+        "no connection",
+        "connection lost",
+        "operation unsupported",
+        "invalid handle",
+        "no such path",
+        "file already exists",
+        "file is write protected",
+        "no media available",
+        "no space on filesystem",
+        "quota exceeded",
+        "unknown principal",
+        "lock conflict",
+        "directory not empty",
+        "not a directory",
+        "invalid filename",
+        "link loop",
+    ];
+    if error.raw_os_error().is_some() {
+        return None;
+    }
+    let message = error.to_string();
+    MESSAGES.iter().position(|m| *m == message).map(|i| i as i32 + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ssh2::ErrorCode;
+
+    fn sftp_error(code: i32) -> ssh2::Error {
+        ssh2::Error::from_errno(ErrorCode::SFTP(code))
+    }
+
+    // SFTP 文件读写的错误经过 ssh2 的 io::Error 转换
+    fn sftp_io_error(code: i32) -> anyhow::Error {
+        io::Error::from(sftp_error(code)).into()
+    }
+
+    #[test]
+    fn sftp_failure_is_not_transient() {
+        assert!(!is_transient(&sftp_error(4).into()));
+        assert!(!is_transient(&sftp_io_error(4)));
+        assert!(!is_transient(&sftp_io_error(14)));
+        assert!(is_transient(&sftp_error(7).into()));
+        assert!(is_transient(&sftp_io_error(7)));
+    }
+
+    #[test]
+    fn session_errors_through_io_are_transient() {
+        let socket = io::Error::from(ssh2::Error::from_errno(ErrorCode::Session(-43)));
+        assert!(is_transient(&socket.into()));
+        // 带系统错误码的错误不按 SFTP 错误信息判断，28 是 Linux 和 macOS 上的 ENOSPC
+        assert!(!is_transient(&io::Error::from_raw_os_error(28).into()));
+    }
+
+    #[test]
+    fn classify_maps_low_level_errors() {
+        let classify = |error: anyhow::Error| classify(error, Path::new("local"), "/srv/remote");
+        let not_found = classify(sftp_error(2).into());
+        assert!(matches!(not_found.downcast_ref::<TransferError>(), Some(TransferError::FileNotFound { path }) if path == "/srv/remote"));
+        let denied = classify(sftp_io_error(3));
+        assert!(matches!(denied.downcast_ref::<TransferError>(), Some(TransferError::PermissionDenied { path }) if path == "/srv/remote"));
+        let local = classify(io::Error::from_raw_os_error(13).into());
+        assert!(matches!(local.downcast_ref::<TransferError>(), Some(TransferError::PermissionDenied { path }) if path == "local"));
+        let network = classify(io::Error::from(io::ErrorKind::ConnectionReset).into());
+        assert!(matches!(network.downcast_ref::<TransferError>(), Some(TransferError::NetworkError { .. })));
+        assert!(is_transient(&network));
+        // 无法归类的错误和已经是 TransferError 的错误原样返回
+        assert!(classify(sftp_error(4).into()).downcast_ref::<TransferError>().is_none());
+        let lost = classify(TransferError::ConnectionLost { address: "h:22".into(), attempts: 5 }.into());
+        assert!(matches!(lost.downcast_ref::<TransferError>(), Some(TransferError::ConnectionLost { .. })));
+    }
+}
@@ -1,13 +1,127 @@
 // 文件操作工具
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use sha2::Digest;
 use std::fs::File;
 use std::io::{BufReader, Read};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+
+// 支持的摘要算法
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgorithm {
+    #[default]
+    Md5,
+    Sha256,
+    Blake3,
+    Xxh3,
+}
+
+impl HashAlgorithm {
+    pub fn hasher(self) -> Box<dyn Hasher> {
+        match self {
+            HashAlgorithm::Md5 => Box::new(Md5Hasher(md5::Context::new())),
+            HashAlgorithm::Sha256 => Box::new(Sha256Hasher(sha2::Sha256::new())),
+            HashAlgorithm::Blake3 => Box::new(Blake3Hasher(blake3::Hasher::new())),
+            HashAlgorithm::Xxh3 => Box::new(Xxh3Hasher(xxhash_rust::xxh3::Xxh3::new())),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            HashAlgorithm::Md5 => "md5",
+            HashAlgorithm::Sha256 => "sha256",
+            HashAlgorithm::Blake3 => "blake3",
+            HashAlgorithm::Xxh3 => "xxh3",
+        }
+    }
+
+    // 远程计算同一摘要所用的命令，输出格式为 "<摘要>  <文件名>"
+    pub fn remote_command(self) -> &'static str {
+        match self {
+            HashAlgorithm::Md5 => "md5sum",
+            HashAlgorithm::Sha256 => "sha256sum",
+            HashAlgorithm::Blake3 => "b3sum",
+            HashAlgorithm::Xxh3 => "xxhsum -H3",
+        }
+    }
+
+    // 十六进制摘要的长度
+    pub fn hex_len(self) -> usize {
+        match self {
+            HashAlgorithm::Md5 => 32,
+            HashAlgorithm::Sha256 | HashAlgorithm::Blake3 => 64,
+            HashAlgorithm::Xxh3 => 16,
+        }
+    }
+}
+
+// 增量计算摘要，结果为小写十六进制字符串
+pub trait Hasher: Send {
+    fn update(&mut self, data: &[u8]);
+    fn finalize(self: Box<Self>) -> String;
+}
+
+struct Md5Hasher(md5::Context);
+
+impl Hasher for Md5Hasher {
+    fn update(&mut self, data: &[u8]) {
+        self.0.consume(data);
+    }
+
+    fn finalize(self: Box<Self>) -> String {
+        format!("{:x}", self.0.compute())
+    }
+}
+
+struct Sha256Hasher(sha2::Sha256);
+
+impl Hasher for Sha256Hasher {
+    fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    fn finalize(self: Box<Self>) -> String {
+        hex::encode(self.0.finalize())
+    }
+}
+
+struct Blake3Hasher(blake3::Hasher);
+
+impl Hasher for Blake3Hasher {
+    fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    fn finalize(self: Box<Self>) -> String {
+        self.0.finalize().to_hex().to_string()
+    }
+}
+
+struct Xxh3Hasher(xxhash_rust::xxh3::Xxh3);
+
+impl Hasher for Xxh3Hasher {
+    fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    fn finalize(self: Box<Self>) -> String {
+        format!("{:016x}", self.0.digest())
+    }
+}
 
 pub fn calculate_md5<P: AsRef<Path>>(file_path: P) -> Result<String> {
+    calculate_hash(file_path, HashAlgorithm::Md5)
+}
+
+pub fn calculate_hash<P: AsRef<Path>>(file_path: P, algorithm: HashAlgorithm) -> Result<String> {
     let file = File::open(file_path)?;
-    let mut reader = BufReader::new(file);
-    let mut hasher = md5::Context::new();
+    hash_reader(BufReader::new(file), algorithm)
+}
+
+// 计算任意数据流的摘要，用于远程文件通过SFTP读回时的校验
+pub fn hash_reader<R: Read>(mut reader: R, algorithm: HashAlgorithm) -> Result<String> {
+    let mut hasher = algorithm.hasher();
     let mut buffer = [0; 8192];
 
     loop {
@@ -15,11 +129,29 @@ pub fn calculate_md5<P: AsRef<Path>>(file_path: P) -> Result<String> {
         if bytes_read == 0 {
             break;
         }
-        hasher.consume(&buffer[..bytes_read]);
+        hasher.update(&buffer[..bytes_read]);
     }
 
-    let digest = hasher.compute();
-    Ok(format!("{:x}", digest))
+    Ok(hasher.finalize())
+}
+
+// 传输过程中使用的隐藏临时文件，例如 dir/.name.ssh-transfer.part
+pub fn part_path<P: AsRef<Path>>(file_path: P) -> PathBuf {
+    let path = file_path.as_ref();
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{}.ssh-transfer.part", file_name))
+}
+
+// 是否是传输过程中的临时文件或其断点记录
+pub fn is_part_file(file_name: &str) -> bool {
+    file_name.starts_with('.') && file_name.contains(".ssh-transfer.part")
+}
+
+// 传输完成后把临时文件重命名为目标文件
+pub fn commit_part<P: AsRef<Path>>(file_path: P) -> Result<()> {
+    let path = file_path.as_ref();
+    std::fs::rename(part_path(path), path)?;
+    Ok(())
 }
 
 pub fn ensure_parent_dir<P: AsRef<Path>>(file_path: P) -> Result<()> {
@@ -27,4 +159,36 @@ pub fn ensure_parent_dir<P: AsRef<Path>>(file_path: P) -> Result<()> {
         std::fs::create_dir_all(parent)?;
     }
     Ok(())
-}
\ No newline at end of file
+}
+
+// 定位写入：多个线程可以同时向同一个文件的不同位置写入
+#[cfg(unix)]
+pub fn write_all_at(file: &File, buf: &[u8], offset: u64) -> Result<()> {
+    use std::os::unix::fs::FileExt;
+    file.write_all_at(buf, offset)?;
+    Ok(())
+}
+
+#[cfg(windows)]
+pub fn write_all_at(file: &File, mut buf: &[u8], mut offset: u64) -> Result<()> {
+    use std::os::windows::fs::FileExt;
+    while !buf.is_empty() {
+        let written = file.seek_write(buf, offset)?;
+        if written == 0 {
+            return Err(std::io::Error::from(std::io::ErrorKind::WriteZero).into());
+        }
+        buf = &buf[written..];
+        offset += written as u64;
+    }
+    Ok(())
+}
+
+// 文件的修改时间（Unix 秒），无法获取时返回 0
+pub fn mtime_secs(metadata: &std::fs::Metadata) -> u64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
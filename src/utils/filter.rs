@@ -0,0 +1,339 @@
+// 递归传输时的包含/排除规则
+use anyhow::{Context, Result};
+use globset::{GlobBuilder, GlobMatcher};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+// 单条规则：不含 '/' 的模式匹配任意层级的文件名，含 '/' 的模式匹配相对传输根目录的路径
+#[derive(Debug, Clone)]
+struct Rule {
+    matcher: GlobMatcher,
+    dir_only: bool,
+    match_path: bool,
+}
+
+impl Rule {
+    fn parse(pattern: &str) -> Result<Self> {
+        let mut pattern = pattern.trim();
+        // "dir/" 只匹配目录，"dir/**" 等价于排除整个目录
+        let mut dir_only = false;
+        if let Some(stripped) = pattern.strip_suffix("/**") {
+            pattern = stripped;
+            dir_only = true;
+        }
+        if let Some(stripped) = pattern.strip_suffix('/') {
+            pattern = stripped;
+            dir_only = true;
+        }
+        let match_path = pattern.contains('/');
+        let pattern = pattern.trim_start_matches('/');
+
+        let matcher = GlobBuilder::new(pattern)
+            .literal_separator(true)
+            .build()
+            .with_context(|| format!("Invalid glob pattern: {}", pattern))?
+            .compile_matcher();
+        Ok(Self { matcher, dir_only, match_path })
+    }
+
+    fn matches(&self, relative: &str, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        if self.match_path {
+            self.matcher.is_match(relative)
+        } else {
+            self.matcher.is_match(relative.rsplit('/').next().unwrap_or(relative))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PathFilter {
+    includes: Vec<Rule>,
+    excludes: Vec<Rule>,
+}
+
+impl PathFilter {
+    // exclude_from 文件每行一个模式，忽略空行和 # 开头的注释
+    pub fn new(includes: &[String], excludes: &[String], exclude_from: Option<&Path>) -> Result<Self> {
+        let mut exclude_patterns = excludes.to_vec();
+        if let Some(path) = exclude_from {
+            let content = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read exclude file: {}", path.display()))?;
+            exclude_patterns.extend(
+                content
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                    .map(String::from),
+            );
+        }
+
+        Ok(Self {
+            includes: includes.iter().map(|p| Rule::parse(p)).collect::<Result<_>>()?,
+            excludes: exclude_patterns.iter().map(|p| Rule::parse(p)).collect::<Result<_>>()?,
+        })
+    }
+
+    // 判断相对传输根目录的条目是否需要传输
+    // 匹配 include 的条目总是传输；匹配 exclude 的目录整棵跳过
+    // 指定了 include 时其余文件不传输，但仍会进入目录查找匹配的文件
+    pub fn allows(&self, relative: &str, is_dir: bool) -> bool {
+        if self.includes.iter().any(|rule| rule.matches(relative, is_dir)) {
+            return true;
+        }
+        if self.excludes.iter().any(|rule| rule.matches(relative, is_dir)) {
+            return false;
+        }
+        is_dir || self.includes.is_empty()
+    }
+}
+
+// 拼接相对传输根目录的路径
+pub fn join_relative(parent: &str, name: &str) -> String {
+    if parent.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}/{}", parent, name)
+    }
+}
+
+// 上传目录时逐层累积的 .gitignore/.ignore 规则，未启用时不忽略任何条目
+#[derive(Debug, Clone, Default)]
+pub struct IgnoreStack {
+    enabled: bool,
+    // 上传根目录及其规范化的绝对路径，用于把条目路径换算到上层目录的规则中匹配
+    root: PathBuf,
+    canonical_root: PathBuf,
+    // 上传根目录之外的规则，由外到内依次为全局排除规则、.git/info/exclude 和上层目录中的忽略文件
+    parents: Vec<Arc<Gitignore>>,
+    layers: Vec<Arc<Gitignore>>,
+}
+
+impl IgnoreStack {
+    // 启用时先加载全局 git 排除规则（core.excludesFile），
+    // 再从上传根目录向上查找到包含 .git 的仓库根目录，加载沿途的忽略规则
+    pub fn new(enabled: bool, root: &Path) -> Self {
+        let mut stack = Self { enabled, root: root.to_path_buf(), ..Self::default() };
+        if !enabled {
+            return stack;
+        }
+        let (global, error) = Gitignore::global();
+        if let Some(e) = error {
+            log::warn!("Failed to read global git excludes: {}", e);
+        }
+        if !global.is_empty() {
+            stack.parents.push(Arc::new(global));
+        }
+
+        let Ok(canonical_root) = root.canonicalize() else {
+            return stack;
+        };
+        let Some(repo) = canonical_root.ancestors().find(|dir| dir.join(".git").exists()) else {
+            stack.canonical_root = canonical_root;
+            return stack;
+        };
+        if let Some(exclude) = Self::load(repo, &[repo.join(".git").join("info").join("exclude")]) {
+            stack.parents.push(exclude);
+        }
+        // 上传根目录自身的规则由 descend 加载
+        let mut dirs: Vec<&Path> = canonical_root.ancestors().skip(1).take_while(|dir| dir.starts_with(repo)).collect();
+        dirs.reverse();
+        for dir in dirs {
+            if let Some(layer) = Self::load(dir, &[dir.join(".gitignore"), dir.join(".ignore")]) {
+                stack.parents.push(layer);
+            }
+        }
+        stack.canonical_root = canonical_root;
+        stack
+    }
+
+    // 读取 dir 下的忽略文件，没有任何规则时返回 None，后加入的文件优先级更高
+    fn load(dir: &Path, files: &[PathBuf]) -> Option<Arc<Gitignore>> {
+        let mut builder = GitignoreBuilder::new(dir);
+        for file in files {
+            if file.is_file() {
+                if let Some(e) = builder.add(file) {
+                    log::warn!("Problem reading {}: {}", file.display(), e);
+                }
+            }
+        }
+        match builder.build() {
+            Ok(gitignore) if !gitignore.is_empty() => Some(Arc::new(gitignore)),
+            Ok(_) => None,
+            Err(e) => {
+                log::warn!("Invalid ignore rules in {}: {}", dir.display(), e);
+                None
+            }
+        }
+    }
+
+    // 进入目录时加入该目录下的 .gitignore 和 .ignore，.ignore 的优先级更高
+    pub fn descend(&self, dir: &Path) -> Self {
+        let mut stack = self.clone();
+        if !self.enabled {
+            return stack;
+        }
+        if let Some(layer) = Self::load(dir, &[dir.join(".gitignore"), dir.join(".ignore")]) {
+            stack.layers.push(layer);
+        }
+        stack
+    }
+
+    // 由最内层的规则决定，"!pattern" 可以重新包含外层忽略的条目
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        // 上传根目录之外的规则按绝对路径匹配
+        let absolute = match path.strip_prefix(&self.root) {
+            Ok(relative) if !self.canonical_root.as_os_str().is_empty() => self.canonical_root.join(relative),
+            _ => path.to_path_buf(),
+        };
+        let inner = self.layers.iter().rev().map(|layer| layer.matched(path, is_dir));
+        let outer = self.parents.iter().rev().map(|layer| layer.matched(&absolute, is_dir));
+        for matched in inner.chain(outer) {
+            if matched.is_ignore() {
+                return true;
+            }
+            if matched.is_whitelist() {
+                return false;
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("ssh-transfer-filter-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn patterns(list: &[&str]) -> Vec<String> {
+        list.iter().map(|p| p.to_string()).collect()
+    }
+
+    // 模拟递归传输：被拒绝的目录不再进入，返回最终传输的文件
+    fn walk(filter: &PathFilter, tree: &[&str]) -> Vec<String> {
+        let mut selected = Vec::new();
+        for entry in tree {
+            let mut allowed = true;
+            let mut relative = String::new();
+            let components: Vec<&str> = entry.split('/').collect();
+            for (i, name) in components.iter().enumerate() {
+                relative = join_relative(&relative, name);
+                if !filter.allows(&relative, i + 1 < components.len()) {
+                    allowed = false;
+                    break;
+                }
+            }
+            if allowed {
+                selected.push(entry.to_string());
+            }
+        }
+        selected
+    }
+
+    #[test]
+    fn include_takes_precedence_over_exclude() {
+        let filter = PathFilter::new(&patterns(&["*.rs"]), &patterns(&["*.rs", "*.tmp"]), None).unwrap();
+        assert!(filter.allows("main.rs", false));
+        assert!(filter.allows("src/lib.rs", false));
+        assert!(!filter.allows("notes.tmp", false));
+        // 指定了 include 时其余文件不传输，但目录仍会进入
+        assert!(!filter.allows("readme.md", false));
+        assert!(filter.allows("src", true));
+
+        let filter = PathFilter::new(&[], &patterns(&["*.log"]), None).unwrap();
+        assert!(filter.allows("readme.md", false));
+        assert!(!filter.allows("logs/app.log", false));
+    }
+
+    #[test]
+    fn path_patterns_and_directory_only_rules() {
+        let filter = PathFilter::new(&[], &patterns(&["docs/*.md", "cache/"]), None).unwrap();
+        assert!(!filter.allows("docs/guide.md", false));
+        // 含 '/' 的模式相对传输根目录匹配，不匹配更深层的同名路径
+        assert!(filter.allows("src/docs/guide.md", false));
+        assert!(filter.allows("guide.md", false));
+        assert!(!filter.allows("cache", true));
+        assert!(filter.allows("cache", false));
+
+        assert!(PathFilter::new(&[], &patterns(&["[invalid"]), None).is_err());
+    }
+
+    #[test]
+    fn exclude_from_skips_comments_and_blank_lines() {
+        let path = std::env::temp_dir().join(format!("ssh-transfer-filter-{}-exclude.txt", std::process::id()));
+        std::fs::write(&path, "# build output\n\ntarget/\n  *.o  \n#*.rs\n").unwrap();
+        let filter = PathFilter::new(&[], &patterns(&["*.bak"]), Some(&path)).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(!filter.allows("target", true));
+        assert!(!filter.allows("obj/main.o", false));
+        assert!(!filter.allows("old.bak", false));
+        assert!(filter.allows("main.rs", false));
+        assert!(filter.allows("# build output", false));
+
+        assert!(PathFilter::new(&[], &[], Some(Path::new("/nonexistent/exclude.txt"))).is_err());
+    }
+
+    #[test]
+    fn excluded_directory_prunes_whole_subtree() {
+        let tree = [
+            "src/main.rs",
+            "target/debug/app",
+            "target/debug/keep.rs",
+            "node_modules/pkg/index.js",
+            "web/node_modules/pkg/index.js",
+            "web/app.js",
+        ];
+        let filter = PathFilter::new(&[], &patterns(&["target/**", "node_modules"]), None).unwrap();
+        assert_eq!(walk(&filter, &tree), ["src/main.rs", "web/app.js"]);
+
+        // include 只对进入的目录生效，被排除目录中的匹配文件不会传输
+        let filter = PathFilter::new(&patterns(&["*.rs"]), &patterns(&["target/"]), None).unwrap();
+        assert_eq!(walk(&filter, &tree), ["src/main.rs"]);
+    }
+
+    #[test]
+    fn ignore_stack_loads_rules_up_to_repository_root() {
+        let repo = temp_dir("repo");
+        std::fs::create_dir_all(repo.join(".git").join("info")).unwrap();
+        std::fs::write(repo.join(".git").join("info").join("exclude"), "*.tmp\n").unwrap();
+        std::fs::write(repo.join(".gitignore"), "*.log\n/top.txt\n").unwrap();
+        let root = repo.join("project");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join(".gitignore"), "!keep.log\n").unwrap();
+
+        let stack = IgnoreStack::new(true, &root).descend(&root);
+        assert!(stack.is_ignored(&root.join("debug.log"), false));
+        assert!(stack.is_ignored(&root.join("scratch.tmp"), false));
+        // 内层的 "!pattern" 重新包含上层忽略的文件
+        assert!(!stack.is_ignored(&root.join("keep.log"), false));
+        // 锚定在仓库根目录的规则不影响子目录中的同名文件
+        assert!(!stack.is_ignored(&root.join("top.txt"), false));
+        assert!(!stack.is_ignored(&root.join("main.rs"), false));
+
+        // 未启用时不加载任何规则
+        assert!(!IgnoreStack::new(false, &root).descend(&root).is_ignored(&root.join("debug.log"), false));
+        std::fs::remove_dir_all(&repo).unwrap();
+    }
+
+    #[test]
+    fn ignore_stack_skips_parents_outside_a_repository() {
+        let outer = temp_dir("outer");
+        std::fs::write(outer.join(".gitignore"), "*.log\n").unwrap();
+        let root = outer.join("project");
+        std::fs::create_dir_all(&root).unwrap();
+
+        let stack = IgnoreStack::new(true, &root).descend(&root);
+        assert!(!stack.is_ignored(&root.join("debug.log"), false));
+        std::fs::remove_dir_all(&outer).unwrap();
+    }
+}
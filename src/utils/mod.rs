@@ -1,3 +1,9 @@
 // 工具模块入口
 pub mod error;
-pub mod file;
\ No newline at end of file
+pub mod file;
+pub mod filter;
+pub mod rate;
+pub mod retry;
+pub mod schedule;
+pub mod sparse;
+pub mod space;
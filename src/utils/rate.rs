@@ -0,0 +1,254 @@
+// 带宽限制：所有工作线程共用一个令牌桶，另可为每个文件单独限速
+use crate::utils::schedule::BandwidthSchedule;
+use std::io::{self, Read, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// 解析 "10M"、"512K"、"1.5G" 这样的速率，单位为字节/秒，后缀按 1024 进位
+pub fn parse_rate(value: &str) -> Result<u64, String> {
+    let trimmed = value.trim();
+    let trimmed = trimmed.strip_suffix("/s").unwrap_or(trimmed);
+    let trimmed = trimmed.strip_suffix(['B', 'b']).unwrap_or(trimmed);
+    let (number, multiplier) = match trimmed.chars().last().map(|c| c.to_ascii_uppercase()) {
+        Some('K') => (&trimmed[..trimmed.len() - 1], 1024f64),
+        Some('M') => (&trimmed[..trimmed.len() - 1], 1024f64 * 1024.0),
+        Some('G') => (&trimmed[..trimmed.len() - 1], 1024f64 * 1024.0 * 1024.0),
+        _ => (trimmed, 1f64),
+    };
+    let number: f64 = number.trim().parse()
+        .map_err(|_| format!("invalid rate '{}', expected a number with an optional K, M or G suffix", value))?;
+    let rate = (number * multiplier) as u64;
+    if rate == 0 {
+        return Err(format!("rate '{}' must be greater than zero", value));
+    }
+    Ok(rate)
+}
+
+// 令牌桶，允许最多一秒的突发；令牌不足时记为欠账，由取用的线程睡眠偿还
+pub struct TokenBucket {
+    rate: AtomicU64,
+    state: Mutex<(f64, Instant)>,
+}
+
+impl TokenBucket {
+    pub fn new(rate: u64) -> Self {
+        Self {
+            rate: AtomicU64::new(rate),
+            state: Mutex::new((rate as f64, Instant::now())),
+        }
+    }
+
+    // 传输过程中调整速率，已有的欠账按新速率偿还
+    pub fn set_rate(&self, rate: u64) {
+        self.rate.store(rate, Ordering::Relaxed);
+    }
+
+    pub fn take(&self, bytes: usize) {
+        let wait = {
+            let mut state = self.state.lock().unwrap();
+            let now = Instant::now();
+            let (tokens, last) = *state;
+            let rate = self.rate.load(Ordering::Relaxed) as f64;
+            let refilled = (tokens + now.duration_since(last).as_secs_f64() * rate).min(rate);
+            let remaining = refilled - bytes as f64;
+            *state = (remaining, now);
+            if remaining < 0.0 { -remaining / rate } else { 0.0 }
+        };
+        // 睡眠时不持有锁，其他线程可以继续记账
+        if wait > 0.0 {
+            std::thread::sleep(Duration::from_secs_f64(wait));
+        }
+    }
+}
+
+// 全局限速：取 --limit-rate 与时间表当前限速中较小的一个，时间表每秒重新查询一次
+pub struct GlobalLimit {
+    bucket: TokenBucket,
+    limit: Option<u64>,
+    schedule: Option<BandwidthSchedule>,
+    // 上次查询时间表的时间和当时生效的限速
+    active: Mutex<(Instant, Option<u64>)>,
+}
+
+impl GlobalLimit {
+    fn new(limit: Option<u64>, schedule: Option<BandwidthSchedule>) -> Self {
+        let active = Self::effective(limit, schedule.as_ref());
+        if schedule.is_some() {
+            log::info!("Bandwidth limit: {}", describe(active));
+        }
+        Self {
+            bucket: TokenBucket::new(active.unwrap_or(1)),
+            limit,
+            schedule,
+            active: Mutex::new((Instant::now(), active)),
+        }
+    }
+
+    fn effective(limit: Option<u64>, schedule: Option<&BandwidthSchedule>) -> Option<u64> {
+        match (limit, schedule.and_then(BandwidthSchedule::current_rate)) {
+            (Some(limit), Some(scheduled)) => Some(limit.min(scheduled)),
+            (limit, scheduled) => limit.or(scheduled),
+        }
+    }
+
+    fn current(&self) -> Option<u64> {
+        let mut active = self.active.lock().unwrap();
+        if self.schedule.is_some() && active.0.elapsed() >= Duration::from_secs(1) {
+            let rate = Self::effective(self.limit, self.schedule.as_ref());
+            if rate != active.1 {
+                log::info!("Bandwidth limit changed to {}", describe(rate));
+                if let Some(rate) = rate {
+                    self.bucket.set_rate(rate);
+                }
+            }
+            *active = (Instant::now(), rate);
+        }
+        active.1
+    }
+
+    fn take(&self, bytes: usize) {
+        if self.current().is_some() {
+            self.bucket.take(bytes);
+        }
+    }
+}
+
+fn describe(rate: Option<u64>) -> String {
+    match rate {
+        Some(rate) => format!("{} bytes/s", rate),
+        None => "unlimited".to_string(),
+    }
+}
+
+// 一次传输的限速配置，全局限速在所有线程间共享
+pub struct RateLimiter {
+    global: Option<Arc<GlobalLimit>>,
+    per_file: Option<u64>,
+}
+
+impl RateLimiter {
+    pub fn new(global: Option<u64>, per_file: Option<u64>, schedule: Option<BandwidthSchedule>) -> Self {
+        let global = if global.is_some() || schedule.is_some() {
+            Some(Arc::new(GlobalLimit::new(global, schedule)))
+        } else {
+            None
+        };
+        Self { global, per_file }
+    }
+
+    // 只受全局限速约束，用于同时包含多个文件的归档流
+    pub fn shared(&self) -> Throttle {
+        Throttle {
+            global: self.global.clone(),
+            file: None,
+        }
+    }
+
+    // 为一个文件创建限速器，分块传输时在各分块线程间克隆共享
+    pub fn for_file(&self) -> Throttle {
+        Throttle {
+            global: self.global.clone(),
+            file: self.per_file.map(|rate| Arc::new(TokenBucket::new(rate))),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Throttle {
+    global: Option<Arc<GlobalLimit>>,
+    file: Option<Arc<TokenBucket>>,
+}
+
+impl Throttle {
+    // 传输 bytes 字节之前或之后调用，超出速率时阻塞当前线程
+    pub fn consume(&self, bytes: usize) {
+        if let Some(file) = &self.file {
+            file.take(bytes);
+        }
+        if let Some(global) = &self.global {
+            global.take(bytes);
+        }
+    }
+}
+
+// 对经过的数据限速，用于 exec 通道这类由其他代码驱动读写的流
+pub struct Throttled<T> {
+    inner: T,
+    throttle: Throttle,
+}
+
+impl<T> Throttled<T> {
+    pub fn new(inner: T, throttle: Throttle) -> Self {
+        Self { inner, throttle }
+    }
+}
+
+impl<R: Read> Read for Throttled<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.throttle.consume(n);
+        Ok(n)
+    }
+}
+
+impl<W: Write> Write for Throttled<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.throttle.consume(n);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_rate_suffixes() {
+        assert_eq!(parse_rate("1000"), Ok(1000));
+        assert_eq!(parse_rate("512k"), Ok(512 * 1024));
+        assert_eq!(parse_rate("512K"), Ok(512 * 1024));
+        assert_eq!(parse_rate("10M"), Ok(10 * 1024 * 1024));
+        assert_eq!(parse_rate("10MB/s"), Ok(10 * 1024 * 1024));
+        assert_eq!(parse_rate("1.5G"), Ok(1536 * 1024 * 1024));
+        assert_eq!(parse_rate(" 2 m "), Ok(2 * 1024 * 1024));
+    }
+
+    #[test]
+    fn rejects_invalid_rates() {
+        for value in ["", "M", "fast", "10X", "-1M", "0", "0.0001K"] {
+            assert!(parse_rate(value).is_err(), "accepted {:?}", value);
+        }
+    }
+
+    #[test]
+    fn take_within_burst_does_not_wait() {
+        let bucket = TokenBucket::new(1_000_000);
+        let started = Instant::now();
+        bucket.take(500_000);
+        bucket.take(400_000);
+        assert!(started.elapsed() < Duration::from_millis(100));
+    }
+
+    #[test]
+    fn take_larger_than_rate_records_debt() {
+        let bucket = TokenBucket::new(20_000);
+        let started = Instant::now();
+        // 超出一秒的突发量，欠下的 10000 字节按速率睡眠偿还
+        bucket.take(30_000);
+        let first = started.elapsed();
+        assert!(first >= Duration::from_millis(450), "waited {:?}", first);
+        assert!(first < Duration::from_millis(1500), "waited {:?}", first);
+
+        // 欠账已用睡眠偿还，下一次取用只为自己的字节等待
+        bucket.take(2_000);
+        let second = started.elapsed() - first;
+        assert!(second >= Duration::from_millis(80), "waited {:?}", second);
+        assert!(second < Duration::from_millis(1000), "waited {:?}", second);
+    }
+}
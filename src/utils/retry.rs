@@ -0,0 +1,48 @@
+// 失败重试：指数退避加随机抖动，只重试临时错误
+use crate::utils::error::is_transient;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// 单次等待的上限
+const MAX_DELAY: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    retries: u32,
+    delay: Duration,
+}
+
+impl RetryPolicy {
+    pub fn new(retries: u32, delay: Duration) -> Self {
+        Self { retries, delay }
+    }
+
+    // 第 attempt 次重试（从 0 开始）前的等待时间：delay * 2^attempt，再乘以 0.5~1.0 的随机系数
+    // 随机抖动让同时失败的多个线程错开重连时间
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let base = self.delay.saturating_mul(1u32 << attempt.min(16)).min(MAX_DELAY);
+        base.mul_f64(0.5 + jitter() / 2.0)
+    }
+
+    // 失败后是否还应重试，需要重试时返回等待时间
+    pub fn should_retry(&self, attempt: u32, error: &anyhow::Error) -> Option<Duration> {
+        if attempt >= self.retries || !is_transient(error) {
+            return None;
+        }
+        Some(self.backoff(attempt))
+    }
+
+    pub fn report(&self, what: &str, error: &anyhow::Error, attempt: u32, delay: Duration) {
+        eprintln!("{} failed: {}; retrying in {:.1}s ({}/{})",
+                  what, error, delay.as_secs_f64(), attempt + 1, self.retries);
+    }
+}
+
+// 0.0~1.0 之间的伪随机数，抖动不需要密码学强度
+fn jitter() -> f64 {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.subsec_nanos()).unwrap_or(0);
+    let mut x = nanos as u64 ^ 0x9e37_79b9_7f4a_7c15;
+    x ^= x >> 33;
+    x = x.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    x ^= x >> 33;
+    (x % 10_000) as f64 / 10_000.0
+}
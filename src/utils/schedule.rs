@@ -0,0 +1,166 @@
+// 按一天中的时间段切换带宽限制
+use crate::utils::rate::parse_rate;
+use anyhow::{bail, Context, Result};
+use chrono::Timelike;
+use std::path::Path;
+
+// 一个时间段，以当天零点起的分钟数表示，end 小于 start 时跨越午夜
+#[derive(Debug, Clone)]
+struct Window {
+    start: u32,
+    end: u32,
+    // None 表示不限速
+    rate: Option<u64>,
+}
+
+impl Window {
+    fn contains(&self, minute: u32) -> bool {
+        if self.start <= self.end {
+            minute >= self.start && minute < self.end
+        } else {
+            minute >= self.start || minute < self.end
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BandwidthSchedule {
+    windows: Vec<Window>,
+    default: Option<u64>,
+}
+
+impl BandwidthSchedule {
+    // 每行 "HH:MM-HH:MM 速率" 或 "default 速率"，速率可写 unlimited
+    // 忽略空行和 # 开头的注释，多个时间段重叠时以先出现的为准
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read bandwidth schedule: {}", path.display()))?;
+        Self::parse(&content).with_context(|| format!("Invalid bandwidth schedule: {}", path.display()))
+    }
+
+    fn parse(content: &str) -> Result<Self> {
+        let mut windows = Vec::new();
+        let mut default = None;
+        for (number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (range, rate) = line.split_once(char::is_whitespace)
+                .with_context(|| format!("line {}: expected \"HH:MM-HH:MM RATE\" or \"default RATE\"", number + 1))?;
+            let rate = parse_limit(rate.trim()).with_context(|| format!("line {}", number + 1))?;
+            if range == "default" {
+                default = rate;
+                continue;
+            }
+            let (start, end) = range.split_once('-')
+                .with_context(|| format!("line {}: expected a time range such as 08:00-19:00", number + 1))?;
+            let start = parse_time(start).with_context(|| format!("line {}", number + 1))?;
+            let end = parse_time(end).with_context(|| format!("line {}", number + 1))?;
+            // 起止时间相同的时间段无法区分是空还是全天，全天请写 00:00-24:00
+            if start == end {
+                bail!("line {}: time range {} is empty, use 00:00-24:00 for the whole day", number + 1, range);
+            }
+            windows.push(Window { start, end, rate });
+        }
+        Ok(Self { windows, default })
+    }
+
+    // 当前本地时间对应的限速，None 表示不限速
+    pub fn current_rate(&self) -> Option<u64> {
+        let now = chrono::Local::now();
+        self.rate_at(now.hour() * 60 + now.minute())
+    }
+
+    fn rate_at(&self, minute: u32) -> Option<u64> {
+        self.windows.iter()
+            .find(|window| window.contains(minute))
+            .map(|window| window.rate)
+            .unwrap_or(self.default)
+    }
+}
+
+fn parse_limit(value: &str) -> Result<Option<u64>> {
+    if value.eq_ignore_ascii_case("unlimited") {
+        return Ok(None);
+    }
+    parse_rate(value).map(Some).map_err(anyhow::Error::msg)
+}
+
+// 解析 HH:MM，24:00 表示当天结束
+fn parse_time(value: &str) -> Result<u32> {
+    let (hour, minute) = value.trim().split_once(':')
+        .with_context(|| format!("invalid time '{}', expected HH:MM", value))?;
+    let hour: u32 = hour.parse().with_context(|| format!("invalid time '{}'", value))?;
+    let minute: u32 = minute.parse().with_context(|| format!("invalid time '{}'", value))?;
+    if minute >= 60 || hour > 24 || (hour == 24 && minute > 0) {
+        bail!("invalid time '{}'", value);
+    }
+    Ok(hour * 60 + minute)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn minute(hour: u32, minute: u32) -> u32 {
+        hour * 60 + minute
+    }
+
+    #[test]
+    fn parses_windows_defaults_and_comments() {
+        let schedule = BandwidthSchedule::parse(
+            "# office hours\n\n08:00-19:00 1M\n19:00-24:00 unlimited\ndefault 512k\n",
+        ).unwrap();
+        assert_eq!(schedule.rate_at(minute(7, 59)), Some(512 * 1024));
+        assert_eq!(schedule.rate_at(minute(8, 0)), Some(1024 * 1024));
+        assert_eq!(schedule.rate_at(minute(18, 59)), Some(1024 * 1024));
+        assert_eq!(schedule.rate_at(minute(19, 0)), None);
+        assert_eq!(schedule.rate_at(minute(23, 59)), None);
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        for content in [
+            "08:00-19:00",
+            "08:00 1M",
+            "8-19 1M",
+            "08:60-19:00 1M",
+            "25:00-26:00 1M",
+            "24:01-02:00 1M",
+            "08:00-19:00 fast",
+            "00:00-00:00 1M",
+            "12:30-12:30 unlimited",
+        ] {
+            assert!(BandwidthSchedule::parse(content).is_err(), "accepted {:?}", content);
+        }
+        assert!(BandwidthSchedule::parse("00:00-24:00 1M").is_ok());
+    }
+
+    #[test]
+    fn window_wraps_past_midnight() {
+        let schedule = BandwidthSchedule::parse("22:00-06:00 10M").unwrap();
+        assert_eq!(schedule.rate_at(minute(22, 0)), Some(10 * 1024 * 1024));
+        assert_eq!(schedule.rate_at(minute(23, 59)), Some(10 * 1024 * 1024));
+        assert_eq!(schedule.rate_at(minute(0, 0)), Some(10 * 1024 * 1024));
+        assert_eq!(schedule.rate_at(minute(5, 59)), Some(10 * 1024 * 1024));
+        assert_eq!(schedule.rate_at(minute(6, 0)), None);
+        assert_eq!(schedule.rate_at(minute(21, 59)), None);
+    }
+
+    #[test]
+    fn first_overlapping_window_wins() {
+        let schedule = BandwidthSchedule::parse("09:00-12:00 1M\n08:00-18:00 2M\n").unwrap();
+        assert_eq!(schedule.rate_at(minute(8, 30)), Some(2 * 1024 * 1024));
+        assert_eq!(schedule.rate_at(minute(10, 0)), Some(1024 * 1024));
+        assert_eq!(schedule.rate_at(minute(12, 0)), Some(2 * 1024 * 1024));
+    }
+
+    #[test]
+    fn unlimited_outside_windows_without_default() {
+        let schedule = BandwidthSchedule::parse("08:00-19:00 1M").unwrap();
+        assert_eq!(schedule.rate_at(minute(3, 0)), None);
+        assert_eq!(schedule.rate_at(minute(19, 0)), None);
+        assert_eq!(BandwidthSchedule::parse("").unwrap().rate_at(minute(12, 0)), None);
+    }
+}
@@ -0,0 +1,85 @@
+// 传输前检查目标端的可用空间，以及预分配目标文件
+use crate::ssh::client::shell_quote;
+use crate::ssh::SshClient;
+use crate::utils::error::TransferError;
+use anyhow::Result;
+use ssh2::Sftp;
+use std::fs::File;
+use std::path::Path;
+
+// 本地路径所在文件系统的可用字节数，路径还不存在时查询最近的已存在上级目录
+// 无法获取时返回 None
+pub fn local_available(path: &Path) -> Option<u64> {
+    let existing = path.ancestors().find(|ancestor| ancestor.exists())?;
+    statvfs_available(existing)
+}
+
+#[cfg(unix)]
+fn statvfs_available(path: &Path) -> Option<u64> {
+    use std::os::unix::ffi::OsStrExt;
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+#[cfg(not(unix))]
+fn statvfs_available(_path: &Path) -> Option<u64> {
+    None
+}
+
+// 远程路径所在文件系统的可用字节数：优先使用 statvfs@openssh.com 扩展，不支持时执行 df
+pub fn remote_available(client: &SshClient, sftp: &Sftp, remote_path: &str) -> Option<u64> {
+    let mut existing = Path::new(remote_path);
+    while sftp.stat(existing).is_err() {
+        existing = existing.parent()?;
+    }
+
+    // OpenSSH 只支持对文件句柄查询，以只读方式打开目录即可得到句柄
+    if let Ok(stat) = sftp.open(existing).and_then(|mut handle| handle.statvfs()) {
+        return Some(stat.f_bavail * stat.f_frsize);
+    }
+
+    // POSIX 格式输出：文件系统 1024-blocks 已用 可用 使用率 挂载点
+    let output = client.exec(&format!("df -Pk {} 2>/dev/null", shell_quote(&existing.to_string_lossy()))).ok()?;
+    let available = output.lines().nth(1)?.split_whitespace().nth(3)?.parse::<u64>().ok()?;
+    Some(available * 1024)
+}
+
+// 可用空间不足时返回 InsufficientSpace，无法获取可用空间时不做限制
+pub fn ensure_space(path: &str, needed: u64, available: Option<u64>) -> Result<()> {
+    match available {
+        Some(available) if available < needed => Err(TransferError::InsufficientSpace {
+            path: path.to_string(),
+            needed,
+            available,
+        }.into()),
+        Some(_) => Ok(()),
+        None => {
+            log::debug!("Could not determine free space for {}, skipping the check", path);
+            Ok(())
+        }
+    }
+}
+
+// 为目标文件预先分配空间，减少碎片；文件系统不支持时只给出警告
+pub fn preallocate(file: &File, size: u64) {
+    if size == 0 {
+        return;
+    }
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+    {
+        use std::os::unix::io::AsRawFd;
+        let result = unsafe { libc::posix_fallocate(file.as_raw_fd(), 0, size as libc::off_t) };
+        if result != 0 {
+            log::warn!("Could not preallocate {} bytes: {}", size, std::io::Error::from_raw_os_error(result));
+        }
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "android", target_os = "freebsd")))]
+    {
+        let _ = file;
+        log::debug!("Preallocation is not supported on this platform");
+    }
+}
@@ -0,0 +1,305 @@
+// 稀疏文件支持：读取时跳过空洞，写入时把全零块变成空洞
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+// 判断和跳过全零数据的粒度，与常见文件系统块大小一致
+const BLOCK_SIZE: usize = 4096;
+
+fn is_zero(buf: &[u8]) -> bool {
+    buf.iter().all(|&byte| byte == 0)
+}
+
+// 按块把缓冲区切分为连续的 (是否全零, 起始位置, 结束位置) 区段
+fn runs(buf: &[u8]) -> Vec<(bool, usize, usize)> {
+    let mut runs: Vec<(bool, usize, usize)> = Vec::new();
+    for (index, block) in buf.chunks(BLOCK_SIZE).enumerate() {
+        let start = index * BLOCK_SIZE;
+        let zero = is_zero(block);
+        match runs.last_mut() {
+            Some(last) if last.0 == zero => last.2 = start + block.len(),
+            _ => runs.push((zero, start, start + block.len())),
+        }
+    }
+    runs
+}
+
+// 用 SEEK_DATA/SEEK_HOLE 找出文件中的数据区段，文件系统不支持时返回 None
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+fn data_extents(file: &File, size: u64) -> Option<Vec<(u64, u64)>> {
+    use std::os::unix::io::AsRawFd;
+    let fd = file.as_raw_fd();
+    let mut extents = Vec::new();
+    let mut position = 0i64;
+    while (position as u64) < size {
+        let data = unsafe { libc::lseek(fd, position, libc::SEEK_DATA) };
+        if data < 0 {
+            // ENXIO 表示之后只剩空洞
+            return match io::Error::last_os_error().raw_os_error() {
+                Some(libc::ENXIO) => Some(extents),
+                _ => None,
+            };
+        }
+        let hole = unsafe { libc::lseek(fd, data, libc::SEEK_HOLE) };
+        if hole < 0 {
+            return None;
+        }
+        extents.push((data as u64, (hole as u64).min(size)));
+        position = hole;
+    }
+    Some(extents)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "freebsd")))]
+fn data_extents(_file: &File, _size: u64) -> Option<Vec<(u64, u64)>> {
+    None
+}
+
+// 读取本地文件时空洞部分直接填零，不再读取磁盘
+pub struct SparseReader {
+    file: File,
+    // None 表示整个文件都按数据读取
+    extents: Option<Vec<(u64, u64)>>,
+    size: u64,
+    position: u64,
+    // 刚跳过空洞，底层文件的读取位置需要重新定位
+    stale: bool,
+}
+
+impl SparseReader {
+    pub fn new(mut file: File, enabled: bool) -> io::Result<Self> {
+        let size = file.metadata()?.len();
+        let extents = if enabled { data_extents(&file, size) } else { None };
+        // 探测空洞会移动文件位置
+        file.seek(SeekFrom::Start(0))?;
+        Ok(Self { file, extents, size, position: 0, stale: false })
+    }
+
+    // 当前位置在空洞中时返回空洞的结束位置
+    fn hole_end(&self) -> Option<u64> {
+        let extents = self.extents.as_ref()?;
+        if self.position >= self.size {
+            return None;
+        }
+        match extents.iter().find(|(_, end)| *end > self.position) {
+            Some((start, _)) if *start > self.position => Some(*start),
+            Some(_) => None,
+            None => Some(self.size),
+        }
+    }
+}
+
+impl Read for SparseReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if let Some(hole_end) = self.hole_end() {
+            let n = ((hole_end - self.position) as usize).min(buf.len());
+            buf[..n].fill(0);
+            self.position += n as u64;
+            self.stale = true;
+            return Ok(n);
+        }
+        if self.stale {
+            self.file.seek(SeekFrom::Start(self.position))?;
+            self.stale = false;
+        }
+        // 只读到当前数据区段结束，下一次读取再处理后面的空洞
+        let limit = self.extents.as_ref()
+            .and_then(|extents| extents.iter().find(|(_, end)| *end > self.position))
+            .map(|(_, end)| ((end - self.position) as usize).min(buf.len()))
+            .unwrap_or(buf.len());
+        let n = self.file.read(&mut buf[..limit])?;
+        self.position += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for SparseReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        // 跳过空洞后底层文件的位置不可信，相对定位换算为绝对位置
+        let pos = match pos {
+            SeekFrom::Current(offset) => SeekFrom::Start((self.position as i64 + offset) as u64),
+            pos => pos,
+        };
+        self.position = self.file.seek(pos)?;
+        self.stale = false;
+        Ok(self.position)
+    }
+}
+
+// 写入新建的目标文件时用定位代替全零块的写入，目标保持稀疏
+// 只能用于刚创建或截断的文件，否则跳过的位置会保留旧数据
+pub struct SparseWriter<W> {
+    inner: W,
+    enabled: bool,
+    position: u64,
+    // 最后一段是跳过的全零块，结束时需要补齐文件长度
+    trailing_hole: bool,
+}
+
+impl<W: Write + Seek> SparseWriter<W> {
+    pub fn new(mut inner: W, enabled: bool) -> io::Result<Self> {
+        let position = inner.stream_position()?;
+        Ok(Self { inner, enabled, position, trailing_hole: false })
+    }
+
+    pub fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        if !self.enabled {
+            self.inner.write_all(buf)?;
+            self.position += buf.len() as u64;
+            return Ok(());
+        }
+        for (zero, start, end) in runs(buf) {
+            if zero {
+                self.trailing_hole = true;
+            } else {
+                if self.trailing_hole {
+                    self.inner.seek(SeekFrom::Start(self.position + start as u64))?;
+                    self.trailing_hole = false;
+                }
+                self.inner.write_all(&buf[start..end])?;
+            }
+        }
+        self.position += buf.len() as u64;
+        Ok(())
+    }
+
+    // 以空洞结尾时在最后写入一个零字节，使文件长度正确
+    pub fn finish(&mut self) -> io::Result<()> {
+        if self.trailing_hole && self.position > 0 {
+            self.inner.seek(SeekFrom::Start(self.position - 1))?;
+            self.inner.write_all(&[0])?;
+            self.trailing_hole = false;
+        }
+        self.inner.flush()
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+}
+
+// 定位写入的稀疏版本，用于已经用 set_len 预分配长度的新文件
+pub fn write_sparse_at(file: &File, buf: &[u8], offset: u64) -> anyhow::Result<()> {
+    for (zero, start, end) in runs(buf) {
+        if !zero {
+            crate::utils::file::write_all_at(file, &buf[start..end], offset + start as u64)?;
+        }
+    }
+    Ok(())
+}
+
+// 检查磁盘占用依赖 Unix 的 blocks()
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::MetadataExt;
+    use std::path::PathBuf;
+
+    const MIB: usize = 1024 * 1024;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("ssh-transfer-sparse-{}-{}", std::process::id(), name))
+    }
+
+    // 1 MiB 全零、一个数据块、再 1 MiB 全零
+    fn holey_data() -> Vec<u8> {
+        let mut data = vec![0u8; 2 * MIB + BLOCK_SIZE];
+        data[MIB..MIB + BLOCK_SIZE].fill(0xAB);
+        data
+    }
+
+    #[test]
+    fn runs_merge_consecutive_blocks() {
+        assert!(runs(&[]).is_empty());
+        assert_eq!(runs(&[0u8; 3 * BLOCK_SIZE]), vec![(true, 0, 3 * BLOCK_SIZE)]);
+
+        let mut buf = vec![0u8; 4 * BLOCK_SIZE + 10];
+        buf[BLOCK_SIZE + 1] = 1;
+        buf[2 * BLOCK_SIZE] = 1;
+        buf[4 * BLOCK_SIZE + 9] = 1;
+        assert_eq!(runs(&buf), vec![
+            (true, 0, BLOCK_SIZE),
+            (false, BLOCK_SIZE, 3 * BLOCK_SIZE),
+            (true, 3 * BLOCK_SIZE, 4 * BLOCK_SIZE),
+            // 不足一块的尾部单独判断
+            (false, 4 * BLOCK_SIZE, 4 * BLOCK_SIZE + 10),
+        ]);
+    }
+
+    #[test]
+    fn sparse_writer_leaves_holes() {
+        let data = holey_data();
+        let path = temp_path("holes");
+        let mut writer = SparseWriter::new(File::create(&path).unwrap(), true).unwrap();
+        // 分多次写入，空洞跨越写入边界
+        for piece in data.chunks(100_000) {
+            writer.write_all(piece).unwrap();
+        }
+        writer.finish().unwrap();
+        drop(writer);
+
+        let metadata = std::fs::metadata(&path).unwrap();
+        let content = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(metadata.len(), data.len() as u64);
+        assert!(content == data);
+        // 实际占用的磁盘空间远小于文件长度
+        assert!(metadata.blocks() * 512 < MIB as u64, "{} blocks allocated", metadata.blocks());
+    }
+
+    #[test]
+    fn disabled_writer_writes_zeros() {
+        let data = holey_data();
+        let path = temp_path("dense");
+        let mut writer = SparseWriter::new(File::create(&path).unwrap(), false).unwrap();
+        writer.write_all(&data).unwrap();
+        writer.finish().unwrap();
+        drop(writer);
+
+        let metadata = std::fs::metadata(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(metadata.len(), data.len() as u64);
+        assert!(metadata.blocks() * 512 >= data.len() as u64);
+    }
+
+    #[test]
+    fn trailing_hole_sets_final_length() {
+        let path = temp_path("trailing");
+        let mut writer = SparseWriter::new(File::create(&path).unwrap(), true).unwrap();
+        writer.write_all(b"header").unwrap();
+        writer.write_all(&vec![0u8; MIB]).unwrap();
+        writer.finish().unwrap();
+        drop(writer);
+
+        let content = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(content.len(), 6 + MIB);
+        assert_eq!(&content[..6], b"header");
+        assert!(is_zero(&content[6..]));
+
+        // 整个文件都是空洞
+        let path = temp_path("all-hole");
+        let mut writer = SparseWriter::new(File::create(&path).unwrap(), true).unwrap();
+        writer.write_all(&vec![0u8; 3 * BLOCK_SIZE]).unwrap();
+        writer.finish().unwrap();
+        drop(writer);
+        let len = std::fs::metadata(&path).unwrap().len();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(len, 3 * BLOCK_SIZE as u64);
+    }
+
+    #[test]
+    fn sparse_reader_returns_holes_as_zeros() {
+        let data = holey_data();
+        let path = temp_path("reader");
+        let mut writer = SparseWriter::new(File::create(&path).unwrap(), true).unwrap();
+        writer.write_all(&data).unwrap();
+        writer.finish().unwrap();
+        drop(writer);
+
+        let mut reader = SparseReader::new(File::open(&path).unwrap(), true).unwrap();
+        let mut content = Vec::new();
+        reader.read_to_end(&mut content).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(content == data);
+    }
+}